@@ -7,36 +7,130 @@ impl Race {
         Race { time, distance }
     }
 
+    // holding for h gives h * (time - h), so the winning holds are the
+    // integers strictly between the roots of h^2 - time * h + distance = 0
     pub fn count_tactics(&self) -> i64 {
-        (0..=self.time)
-            .filter(|time_held| (time_held * (self.time - time_held)) > self.distance)
-            .count()
-            .try_into()
-            .unwrap()
+        let time = self.time as i128;
+        let distance = self.distance as i128;
+        let discriminant = time * time - 4 * distance;
+
+        if discriminant <= 0 {
+            return 0;
+        }
+
+        let beats = |held: i128| held * (time - held) > distance;
+
+        // the integer root only approximates the real one, nudge it onto
+        // the first hold that strictly beats the record (ties don't count)
+        let mut lowest = ((time - discriminant.isqrt()) / 2).max(0);
+        while lowest > 0 && beats(lowest - 1) {
+            lowest -= 1;
+        }
+        while lowest <= time && !beats(lowest) {
+            lowest += 1;
+        }
+
+        let highest = time - lowest;
+        if highest < lowest {
+            return 0;
+        }
+
+        (highest - lowest + 1).try_into().unwrap()
     }
 }
 
+pub fn part_one(input: &str) -> i64 {
+    parse_races(input, false)
+        .iter()
+        .map(|r| r.count_tactics())
+        .product()
+}
+
+pub fn part_two(input: &str) -> i64 {
+    parse_races(input, true)
+        .iter()
+        .map(|r| r.count_tactics())
+        .product()
+}
+
+// with kerning the spaces between numbers are ignored, collapsing each line
+// into a single value and so the whole input into a single race
+pub fn parse_races(input: &str, kerning: bool) -> Vec<Race> {
+    let mut lines = input.lines().map(|line| {
+        let (_, values) = line.split_once(':').unwrap();
+        if kerning {
+            vec![values.replace(' ', "").parse::<i64>().unwrap()]
+        } else {
+            values
+                .split_whitespace()
+                .map(|v| v.parse::<i64>().unwrap())
+                .collect()
+        }
+    });
+
+    let times = lines.next().unwrap();
+    let distances = lines.next().unwrap();
+
+    times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, distance)| Race::new(time, distance))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    static EXAMPLE: &str = include_str!("input.example.txt");
+    static INPUT: &str = include_str!("input.txt");
+
+    fn brute_force(race: &Race) -> i64 {
+        (0..=race.time)
+            .filter(|held| held * (race.time - held) > race.distance)
+            .count() as i64
+    }
+
     #[test]
-    fn test_input() {
-        assert_eq!(
-            114400_i64,
-            [
-                Race::new(35, 212),
-                Race::new(93, 2060),
-                Race::new(73, 1201),
-                Race::new(66, 1044),
-            ]
-            .iter()
-            .map(|r| r.count_tactics())
-            .product()
-        );
+    fn test_part_one() {
+        assert_eq!(288, part_one(EXAMPLE));
+        assert_eq!(114400, part_one(INPUT));
+    }
+
+    #[test]
+    fn test_part_two() {
+        assert_eq!(71503, part_two(EXAMPLE));
+        assert_eq!(21039729, part_two(INPUT));
+    }
+
+    #[test]
+    fn test_parse_races() {
+        let races = parse_races(INPUT, false);
+        assert_eq!(4, races.len());
+        assert_eq!((35, 212), (races[0].time, races[0].distance));
+        assert_eq!((66, 1044), (races[3].time, races[3].distance));
+
+        let races = parse_races(INPUT, true);
+        assert_eq!(1, races.len());
         assert_eq!(
-            21039729,
-            Race::new(35937366, 212206012011044).count_tactics()
+            (35937366, 212206012011044),
+            (races[0].time, races[0].distance)
         );
     }
+
+    #[test]
+    fn test_count_tactics_boundaries() {
+        // 30ms against 200mm ties at 10 and 20, which must not count
+        assert_eq!(9, Race::new(30, 200).count_tactics());
+        assert_eq!(0, Race::new(4, 4).count_tactics());
+        assert_eq!(0, Race::new(3, 10).count_tactics());
+        assert_eq!(0, Race::new(0, 0).count_tactics());
+
+        for time in 0..60 {
+            for distance in 0..(time * time / 4 + 2) {
+                let race = Race::new(time, distance);
+                assert_eq!(brute_force(&race), race.count_tactics());
+            }
+        }
+    }
 }
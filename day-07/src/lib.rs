@@ -1,81 +1,225 @@
 use itertools::Itertools;
-use std::cmp::Ordering;
-use std::collections::HashMap;
 
 pub fn calculate_winnings(input: &str, jokers_wild: bool) -> i32 {
-    let mut hands: Vec<_> = input
-        .lines()
-        .map(|line| Hand::new(line, jokers_wild))
-        .collect();
-    hands.sort_by(|a, b| match a.hand_strength.cmp(&b.hand_strength) {
-        Ordering::Equal => a.cards.cmp(&b.cards),
-        ordering => ordering,
-    });
-    hands
+    let rules = match jokers_wild {
+        true => Rules::camel_cards_jokers(),
+        false => Rules::camel_cards(),
+    };
+    score(input, &rules)
+}
+
+pub fn score(input: &str, rules: &Rules) -> i32 {
+    classify_hands(input, rules)
         .iter()
-        .enumerate()
-        .map(|(rank, hand)| hand.bid_amount * (rank as i32 + 1))
+        .map(|hand| hand.winnings)
         .sum()
 }
 
-struct Hand {
-    cards: Vec<i32>,
-    hand_strength: HandStrength,
-    bid_amount: i32,
+// every hand ordered from weakest to strongest, along with how it was
+// classified, so we can audit why it landed at the rank it did
+pub fn classify_hands(input: &str, rules: &Rules) -> Vec<Classification> {
+    let mut hands: Vec<_> = input.lines().map(|line| Hand::new(line, rules)).collect();
+    hands.sort_by(|a, b| (a.category, &a.tie_break).cmp(&(b.category, &b.tie_break)));
+    hands
+        .into_iter()
+        .enumerate()
+        .map(|(index, hand)| {
+            let rank = index + 1;
+            Classification {
+                winnings: hand.bid_amount * rank as i32,
+                cards: hand.cards,
+                strength: hand.strength,
+                substitution: hand.substitution,
+                rank,
+                bid_amount: hand.bid_amount,
+            }
+        })
+        .collect()
 }
-impl Hand {
-    fn new(input: &str, jokers_wild: bool) -> Hand {
-        let (cards_str, bid_amount) = input.split_once(' ').unwrap();
-        let cards: Vec<_> = cards_str
-            .chars()
-            .map(|c| match c {
-                'A' => 14,
-                'K' => 13,
-                'Q' => 12,
-                'J' => match jokers_wild {
-                    true => 0,
-                    false => 11,
-                },
-                'T' => 10,
-                _ => c.to_digit(10).unwrap() as i32,
-            })
-            .collect();
 
-        let joker_count = cards.iter().filter(|c| **c == 0).count();
+pub fn format_table(hands: &[Classification]) -> String {
+    let mut lines = vec![format!(
+        "{:>5} {:<7} {:<12} {:<5} {:>5} {:>9}",
+        "rank", "cards", "strength", "wild", "bid", "winnings"
+    )];
+    lines.extend(hands.iter().map(|hand| {
+        format!(
+            "{:>5} {:<7} {:<12} {:<5} {:>5} {:>9}",
+            hand.rank,
+            hand.cards,
+            format!("{:?}", hand.strength),
+            hand.substitution.map_or("-".to_string(), |c| c.to_string()),
+            hand.bid_amount,
+            hand.winnings
+        )
+    }));
+    lines.join("\n")
+}
 
-        let binding = cards.iter().fold(HashMap::new(), |mut acc, card| {
-            if 0 != *card {
-                *acc.entry(card).or_insert(0) += 1;
-            }
-            acc
-        });
-        let counts: Vec<_> = binding
-            .values()
-            .filter(|c| **c != 1)
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Classification {
+    pub cards: String,
+    pub strength: HandStrength,
+    // the card any wild cards stood in for, none if the hand had no wilds
+    pub substitution: Option<char>,
+    pub rank: usize,
+    pub bid_amount: i32,
+    pub winnings: i32,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TieBreak {
+    // compare cards in the order they were dealt, as camel cards does
+    Positional,
+    // compare the most repeated cards first, then the highest, as poker does
+    BestCard,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rules {
+    order: Vec<char>,
+    wild: Vec<char>,
+    categories: Vec<HandStrength>,
+    tie_break: TieBreak,
+}
+impl Rules {
+    // both order and categories go from weakest to strongest, any category
+    // left out will never be awarded, the hand falls to the next one it holds,
+    // and a hand holding none of them is a high card ranked below them all
+    pub fn new(
+        order: &str,
+        wild: &str,
+        categories: Vec<HandStrength>,
+        tie_break: TieBreak,
+    ) -> Rules {
+        Rules {
+            order: order.chars().collect(),
+            wild: wild.chars().collect(),
+            categories,
+            tie_break,
+        }
+    }
+
+    pub fn camel_cards() -> Rules {
+        Rules::new(
+            "23456789TJQKA",
+            "",
+            HandStrength::all(),
+            TieBreak::Positional,
+        )
+    }
+
+    pub fn camel_cards_jokers() -> Rules {
+        Rules::new(
+            "J23456789TQKA",
+            "J",
+            HandStrength::all(),
+            TieBreak::Positional,
+        )
+    }
+
+    pub fn poker() -> Rules {
+        Rules::new("23456789TJQKA", "", HandStrength::all(), TieBreak::BestCard)
+    }
+
+    fn value(&self, card: char) -> i32 {
+        match self.order.iter().position(|c| *c == card) {
+            Some(value) => value as i32,
+            None => panic!("card {} is not part of the card order", card),
+        }
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wild.contains(&card)
+    }
+
+    fn classify(&self, cards: &[char]) -> (usize, HandStrength) {
+        let wild_count = cards.iter().filter(|c| self.is_wild(**c)).count();
+        let counts: Vec<_> = cards
+            .iter()
+            .filter(|c| !self.is_wild(**c))
+            .counts()
+            .into_values()
             .sorted()
             .rev()
             .collect();
 
-        let hand_strength = match counts.as_slice() {
-            [5] => HandStrength::FiveOfAKind.add_jokers(joker_count),
-            [4] => HandStrength::FourOfAKind.add_jokers(joker_count),
-            [3, 2] => HandStrength::FullHouse.add_jokers(joker_count),
-            [3] => HandStrength::ThreeOfAKind.add_jokers(joker_count),
-            [2, 2] => HandStrength::TwoPair.add_jokers(joker_count),
-            [2] => HandStrength::OnePair.add_jokers(joker_count),
-            _ => HandStrength::HighCard.add_jokers(joker_count),
-        };
+        self.categories
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, category)| category.is_held(&counts, wild_count))
+            .map(|(rank, category)| (rank + 1, *category))
+            .unwrap_or((0, HandStrength::HighCard))
+    }
+
+    // wild cards always join the largest group, preferring the highest card
+    // on a tie, when everything is wild they become the best card there is
+    fn substitution(&self, cards: &[char]) -> Option<char> {
+        if !cards.iter().any(|c| self.is_wild(*c)) {
+            return None;
+        }
+
+        let counts = cards.iter().filter(|c| !self.is_wild(**c)).counts();
+        match counts
+            .into_iter()
+            .max_by_key(|(card, count)| (*count, self.value(**card)))
+        {
+            Some((card, _)) => Some(*card),
+            None => self
+                .order
+                .iter()
+                .rev()
+                .find(|c| !self.is_wild(**c))
+                .copied(),
+        }
+    }
+
+    fn tie_break(&self, cards: &[char]) -> Vec<i32> {
+        let values: Vec<_> = cards.iter().map(|c| self.value(*c)).collect();
+
+        match self.tie_break {
+            TieBreak::Positional => values,
+            TieBreak::BestCard => {
+                let counts = values.iter().counts();
+                values
+                    .iter()
+                    .sorted_by_key(|v| (counts[v], **v))
+                    .rev()
+                    .copied()
+                    .collect()
+            }
+        }
+    }
+}
+
+struct Hand {
+    cards: String,
+    category: usize,
+    strength: HandStrength,
+    substitution: Option<char>,
+    tie_break: Vec<i32>,
+    bid_amount: i32,
+}
+impl Hand {
+    fn new(input: &str, rules: &Rules) -> Hand {
+        let (cards_str, bid_amount) = input.split_once(' ').unwrap();
+        let cards: Vec<_> = cards_str.chars().collect();
+        let (category, strength) = rules.classify(&cards);
 
         Hand {
-            cards,
-            hand_strength,
+            cards: cards_str.to_string(),
+            category,
+            strength,
+            substitution: rules.substitution(&cards),
+            tie_break: rules.tie_break(&cards),
             bid_amount: bid_amount.parse().unwrap(),
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
-enum HandStrength {
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Copy, Clone)]
+pub enum HandStrength {
     FiveOfAKind = 6,
     FourOfAKind = 5,
     FullHouse = 4,
@@ -85,44 +229,42 @@ enum HandStrength {
     HighCard = 0,
 }
 impl HandStrength {
-    fn add_jokers(&self, amount: usize) -> HandStrength {
-        if amount == 0 {
-            return *self;
-        }
+    pub fn all() -> Vec<HandStrength> {
+        vec![
+            HandStrength::HighCard,
+            HandStrength::OnePair,
+            HandStrength::TwoPair,
+            HandStrength::ThreeOfAKind,
+            HandStrength::FullHouse,
+            HandStrength::FourOfAKind,
+            HandStrength::FiveOfAKind,
+        ]
+    }
 
-        // this could be more clever, but this is also explicit, handle the complexity
-        // around add jokers to something that could be a full house, or a four of kind
+    // the groups of matching cards a hand needs to hold, largest first
+    pub fn groups(&self) -> &'static [usize] {
         match self {
-            HandStrength::FourOfAKind => match amount {
-                1 => HandStrength::FiveOfAKind,
-                _ => panic!("can't add more than 1 jokers to a four of a kind"),
-            },
-            HandStrength::ThreeOfAKind => match amount {
-                1 => HandStrength::FourOfAKind,
-                2 => HandStrength::FiveOfAKind,
-                _ => panic!("can't add more than 2 jokers to a three of a kind"),
-            },
-            HandStrength::TwoPair => match amount {
-                1 => HandStrength::FullHouse,
-                _ => panic!("can't add more than 1 jokers to a two pair"),
-            },
-            HandStrength::OnePair => match amount {
-                1 => HandStrength::ThreeOfAKind,
-                2 => HandStrength::FourOfAKind,
-                3 => HandStrength::FiveOfAKind,
-                _ => panic!("can't add more than 3 jokers to a one pair"),
-            },
-            HandStrength::HighCard => match amount {
-                1 => HandStrength::OnePair,
-                2 => HandStrength::ThreeOfAKind,
-                3 => HandStrength::FourOfAKind,
-                4 => HandStrength::FiveOfAKind,
-                5 => HandStrength::FiveOfAKind,
-                _ => panic!("can't add more than 5 jokers to a high card"),
-            },
-            _ => panic!("can't add a joker to a full hand"),
+            HandStrength::FiveOfAKind => &[5],
+            HandStrength::FourOfAKind => &[4],
+            HandStrength::FullHouse => &[3, 2],
+            HandStrength::ThreeOfAKind => &[3],
+            HandStrength::TwoPair => &[2, 2],
+            HandStrength::OnePair => &[2],
+            HandStrength::HighCard => &[],
         }
     }
+
+    // pairing the largest group we need with the largest group we have leaves
+    // the smallest shortfall, which the wild cards then have to make up
+    fn is_held(&self, counts: &[usize], wild_count: usize) -> bool {
+        let shortfall: usize = self
+            .groups()
+            .iter()
+            .enumerate()
+            .map(|(index, needed)| needed.saturating_sub(*counts.get(index).unwrap_or(&0)))
+            .sum();
+        shortfall <= wild_count
+    }
 }
 
 #[cfg(test)]
@@ -143,4 +285,85 @@ mod tests {
         assert_eq!(5905, calculate_winnings(EXAMPLE, true));
         assert_eq!(254494947, calculate_winnings(INPUT, true));
     }
+
+    #[test]
+    fn configurable_rules() {
+        let classify =
+            |rules: &Rules, cards: &str| rules.classify(&cards.chars().collect::<Vec<_>>()).1;
+
+        let jokers = Rules::camel_cards_jokers();
+        assert_eq!(HandStrength::FiveOfAKind, classify(&jokers, "JJJJJ"));
+        assert_eq!(HandStrength::FullHouse, classify(&jokers, "KKQQJ"));
+        assert_eq!(HandStrength::FourOfAKind, classify(&jokers, "KTJJT"));
+
+        let deuces = Rules::new(
+            "23456789TJQKA",
+            "2J",
+            HandStrength::all(),
+            TieBreak::Positional,
+        );
+        assert_eq!(HandStrength::FiveOfAKind, classify(&deuces, "K2JKK"));
+        assert_eq!(HandStrength::OnePair, classify(&deuces, "A2345"));
+        assert_eq!(HandStrength::FourOfAKind, classify(&deuces, "A22J5"));
+
+        let no_two_pair = Rules::new(
+            "23456789TJQKA",
+            "",
+            vec![
+                HandStrength::HighCard,
+                HandStrength::OnePair,
+                HandStrength::ThreeOfAKind,
+            ],
+            TieBreak::Positional,
+        );
+        assert_eq!(HandStrength::OnePair, classify(&no_two_pair, "KKQQ2"));
+        assert_eq!(HandStrength::ThreeOfAKind, classify(&no_two_pair, "KKKQQ"));
+
+        let pairs_only = Rules::new(
+            "23456789TJQKA",
+            "",
+            vec![HandStrength::OnePair, HandStrength::ThreeOfAKind],
+            TieBreak::Positional,
+        );
+        assert_eq!(HandStrength::HighCard, classify(&pairs_only, "23456"));
+        // the pair ranks 2nd winning 2, the high card ranks 1st winning 2, even
+        // when the high card has the stronger first card
+        assert_eq!(4, score("KKQQ2 1\n23456 2", &pairs_only));
+        assert_eq!(4, score("KKQQ2 1\nA3456 2", &pairs_only));
+
+        // positionally QQ3AA wins on its first card, poker compares the pairs
+        assert_eq!(5, score("2AAKK 1\nQQ3AA 2", &Rules::camel_cards()));
+        assert_eq!(4, score("2AAKK 1\nQQ3AA 2", &Rules::poker()));
+    }
+
+    #[test]
+    fn explains_classifications() {
+        let hands = classify_hands(EXAMPLE, &Rules::camel_cards_jokers());
+        let kings = hands.iter().find(|h| h.cards == "KTJJT").unwrap();
+        assert_eq!(HandStrength::FourOfAKind, kings.strength);
+        assert_eq!(Some('T'), kings.substitution);
+        assert_eq!(5, kings.rank);
+        assert_eq!(1100, kings.winnings);
+
+        let pair = hands.iter().find(|h| h.cards == "32T3K").unwrap();
+        assert_eq!(HandStrength::OnePair, pair.strength);
+        assert_eq!(None, pair.substitution);
+        assert_eq!(1, pair.rank);
+
+        assert_eq!(
+            Some('A'),
+            Rules::camel_cards_jokers().substitution(&['J', 'J', 'J', 'J', 'J'])
+        );
+        assert_eq!(
+            Some('K'),
+            Rules::camel_cards_jokers().substitution(&['K', 'K', 'Q', 'Q', 'J'])
+        );
+
+        let table = format_table(&hands);
+        assert_eq!(6, table.lines().count());
+        assert_eq!(
+            "    5 KTJJT   FourOfAKind  T       220      1100",
+            table.lines().last().unwrap()
+        );
+    }
 }
@@ -1,75 +1,240 @@
-use math::lcm;
-use std::collections::HashMap;
+use graph::export::Export;
+use graph::graph::{Graph, NodeId};
+use math::{crt, lcm};
+use std::collections::{BTreeSet, HashMap};
 
 pub fn part_one(input: &str) -> usize {
-    count_steps_until(&Instructions::new(input), "AAA", "ZZZ")
+    let instructions = Instructions::new(input);
+    let start = instructions.network.id("AAA").unwrap();
+    count_steps_until(&instructions, start, "ZZZ")
 }
 
 pub fn part_two(input: &str) -> usize {
-    let instructions = Instructions::new(input);
-    instructions
-        .steps
+    solve_ghosts(input).expect("ghosts never all reach a Z node together")
+}
+
+pub fn solve_ghosts(input: &str) -> Option<usize> {
+    let cycles = detect_cycles(input);
+
+    // the puzzle inputs are built so every ghost only ever hits a Z node right
+    // as its cycle wraps, in which case the lcm of the cycles is the answer
+    if cycles.iter().all(|c| c.is_clean()) {
+        return Some(cycles.iter().map(|c| c.cycle).fold(1, lcm));
+    }
+
+    cycles
         .iter()
-        .filter(|(label, _)| label.ends_with('A'))
-        .map(|(label, _)| count_steps_until(&instructions, label, "Z"))
-        .fold(1, lcm)
+        .map(Schedule::new)
+        .reduce(|left, right| left.intersect(&right))?
+        .first()
+}
+
+pub fn detect_cycles(input: &str) -> Vec<GhostCycle> {
+    let instructions = Instructions::new(input);
+    let mut starts: Vec<_> = instructions
+        .network
+        .nodes()
+        .filter(|id| instructions.label(*id).ends_with('A'))
+        .collect();
+    starts.sort_by_key(|id| instructions.label(*id));
+
+    starts
+        .into_iter()
+        .map(|start| GhostCycle::new(&instructions, start))
+        .collect()
+}
+
+// a ghost's position only depends on the node it's on and where it is in the
+// instructions, so once that pair repeats the walk loops forever
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct GhostCycle {
+    pub start: String,
+    // steps taken before the walk enters its loop
+    pub prefix: usize,
+    // length of the loop once entered
+    pub cycle: usize,
+    // step counts below prefix + cycle at which the ghost is on a Z node
+    pub hits: Vec<usize>,
+}
+impl GhostCycle {
+    fn new(instructions: &Instructions, start: NodeId) -> GhostCycle {
+        let mut seen: HashMap<(NodeId, usize), usize> = HashMap::new();
+        let mut hits = Vec::new();
+        let mut current_step = start;
+        let mut step_count = 0;
+
+        loop {
+            let index = step_count % instructions.instructions.len();
+            if let Some(prefix) = seen.insert((current_step, index), step_count) {
+                return GhostCycle {
+                    start: instructions.label(start).to_string(),
+                    prefix,
+                    cycle: step_count - prefix,
+                    hits,
+                };
+            }
+
+            if instructions.label(current_step).ends_with('Z') {
+                hits.push(step_count);
+            }
+
+            current_step = instructions.follow(current_step, instructions.instructions[index]);
+            step_count += 1;
+        }
+    }
+
+    // the only hit lands at the end of the first lap, and that hit has to be
+    // inside the loop for it to come around again
+    fn is_clean(&self) -> bool {
+        self.hits == vec![self.cycle] && self.prefix <= self.cycle
+    }
+}
+
+// every step count at which a set of ghosts are all on a Z node, made up of
+// the counts that only happen once, and those that repeat every period
+#[derive(Debug)]
+struct Schedule {
+    once: BTreeSet<usize>,
+    repeating: BTreeSet<usize>,
+    period: usize,
+}
+impl Schedule {
+    fn new(cycle: &GhostCycle) -> Schedule {
+        let (once, repeating) = cycle.hits.iter().partition(|hit| **hit < cycle.prefix);
+        Schedule {
+            once,
+            repeating,
+            period: cycle.cycle,
+        }
+    }
+
+    fn contains(&self, step: usize) -> bool {
+        self.once.contains(&step)
+            || self
+                .repeating
+                .iter()
+                .any(|first| *first <= step && (step - first).is_multiple_of(self.period))
+    }
+
+    fn intersect(&self, other: &Schedule) -> Schedule {
+        let once = self
+            .once
+            .iter()
+            .filter(|step| other.contains(**step))
+            .chain(other.once.iter().filter(|step| self.contains(**step)))
+            .copied()
+            .collect();
+
+        let period = lcm(self.period, other.period);
+        let mut repeating = BTreeSet::new();
+        for left in &self.repeating {
+            for right in &other.repeating {
+                if let Some((value, modulus)) = crt(*left, self.period, *right, other.period) {
+                    // both ghosts only start repeating from their first hit, so
+                    // move forward to the first solution where both have started
+                    let earliest = *left.max(right);
+                    let behind = earliest.saturating_sub(value);
+                    repeating.insert(value + behind.div_ceil(modulus) * modulus);
+                }
+            }
+        }
+
+        Schedule {
+            once,
+            repeating,
+            period,
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.once.iter().chain(self.repeating.iter()).min().copied()
+    }
 }
 
-fn count_steps_until(instructions: &Instructions, starting_step: &str, finish_step: &str) -> usize {
+// the network as a directed graph with edges labeled by the instruction that
+// follows them, ghost start and end nodes are highlighted
+pub fn export(input: &str) -> Export {
+    let instructions = Instructions::new(input);
+    let mut export = Export::new(true);
+
+    for id in instructions.network.nodes() {
+        let label = instructions.label(id);
+        export.node(label);
+        if label.ends_with('A') || label.ends_with('Z') {
+            export.highlight_node(label);
+        }
+
+        let left = instructions.label(instructions.follow(id, true));
+        let right = instructions.label(instructions.follow(id, false));
+        match left == right {
+            true => export.labeled_edge(label, left, "LR"),
+            false => {
+                export.labeled_edge(label, left, "L");
+                export.labeled_edge(label, right, "R");
+            }
+        }
+    }
+
+    export
+}
+
+fn count_steps_until(
+    instructions: &Instructions,
+    starting_step: NodeId,
+    finish_step: &str,
+) -> usize {
     let mut current_step = starting_step;
     let mut step_count = 0;
 
     loop {
-        if current_step.ends_with(finish_step) {
+        if instructions.label(current_step).ends_with(finish_step) {
             return step_count;
         }
 
         let index = step_count % instructions.instructions.len();
-        let direction = &instructions.instructions[index];
-
-        current_step = match direction {
-            true => &instructions.steps[current_step].left,
-            false => &instructions.steps[current_step].right,
-        };
-
+        current_step = instructions.follow(current_step, instructions.instructions[index]);
         step_count += 1;
     }
 }
 
+// edges are weighted by whether they're taken on a left instruction
 struct Instructions {
     instructions: Vec<bool>,
-    steps: HashMap<String, Step>,
+    network: Graph<String, bool>,
 }
 impl Instructions {
     fn new(input: &str) -> Instructions {
         let (instructions, steps) = input.split_once("\n\n").unwrap();
 
+        let mut network = Graph::directed();
+        for line in steps.lines() {
+            let (label, rest) = line.split_once(" = (").unwrap();
+            let (left, right) = rest.split_once(", ").unwrap();
+            network.add_edge(label.to_string(), left.to_string(), true);
+            network.add_edge(
+                label.to_string(),
+                right.trim_end_matches(')').to_string(),
+                false,
+            );
+        }
+
         Instructions {
             instructions: instructions.chars().map(|c| c == 'L').collect(),
-            steps: steps
-                .lines()
-                .map(Step::new)
-                .map(|s| (s.label.to_string(), s))
-                .collect(),
+            network,
         }
     }
-}
 
-struct Step {
-    label: String,
-    left: String,
-    right: String,
-}
-impl Step {
-    fn new(input: &str) -> Step {
-        let (label, rest) = input.split_once(" = (").unwrap();
-        let (left, right) = rest.split_once(", ").unwrap();
-
-        Step {
-            label: label.to_string(),
-            left: left.to_string(),
-            right: right.trim_end_matches(')').to_string(),
-        }
+    fn label(&self, id: NodeId) -> &str {
+        self.network.key(id)
+    }
+
+    fn follow(&self, id: NodeId, left: bool) -> NodeId {
+        self.network
+            .neighbors(id)
+            .iter()
+            .find(|(_, is_left)| *is_left == left)
+            .map(|(next, _)| *next)
+            .unwrap()
     }
 }
 
@@ -92,4 +257,72 @@ mod tests {
         assert_eq!(6, part_two(EXAMPLE_TWO));
         assert_eq!(22103062509257, part_two(INPUT));
     }
+
+    #[test]
+    fn detects_cycles() {
+        let cycles = detect_cycles(EXAMPLE_TWO);
+        assert_eq!(
+            vec![
+                GhostCycle {
+                    start: "11A".to_string(),
+                    prefix: 1,
+                    cycle: 2,
+                    hits: vec![2],
+                },
+                GhostCycle {
+                    start: "22A".to_string(),
+                    prefix: 1,
+                    cycle: 6,
+                    hits: vec![3, 6],
+                },
+            ],
+            cycles
+        );
+    }
+
+    #[test]
+    fn solves_ghosts_without_clean_cycles() {
+        // 11A first reaches Z at 5 then loops every 3 steps, 22A every 2 steps
+        let offset = "L\n\n11A = (11B, XXX)\n11B = (11C, XXX)\n11C = (11D, XXX)\n11D = (11E, XXX)\n11E = (11Z, XXX)\n11Z = (11D, XXX)\n22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22B, XXX)\nXXX = (XXX, XXX)";
+        assert_eq!(Some(8), solve_ghosts(offset));
+
+        // 33A only passes a Z once on its way into its loop
+        let once = format!(
+            "{}\n33A = (33Z, XXX)\n33Z = (33B, XXX)\n33B = (33C, XXX)\n33C = (33B, XXX)",
+            offset
+        );
+        assert_eq!(None, solve_ghosts(&once));
+
+        let misaligned = "L\n\n11A = (11Z, XXX)\n11Z = (11B, XXX)\n11B = (11Z, XXX)\n22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22B, XXX)\nXXX = (XXX, XXX)";
+        assert_eq!(None, solve_ghosts(misaligned));
+
+        // 11A hits a Z at 3, the same as its cycle length, but that's before
+        // its loop starts so it never hits one again
+        let early = "L\n\n11A = (11B, XXX)\n11B = (11C, XXX)\n11C = (11Z, XXX)\n11Z = (11D, XXX)\n11D = (11E, XXX)\n11E = (11F, XXX)\n11F = (11G, XXX)\n11G = (11H, XXX)\n11H = (11F, XXX)\n22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22B, XXX)\nXXX = (XXX, XXX)";
+        assert_eq!(
+            GhostCycle {
+                start: "11A".to_string(),
+                prefix: 6,
+                cycle: 3,
+                hits: vec![3],
+            },
+            detect_cycles(early)[0]
+        );
+        assert_eq!(None, solve_ghosts(early));
+
+        assert_eq!(Some(6), solve_ghosts(EXAMPLE_TWO));
+    }
+
+    #[test]
+    fn exports_network() {
+        let dot = export(EXAMPLE_TWO).to_dot();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("\"11A\" [style=filled, fillcolor=red];"));
+        assert!(dot.contains("\"11B\";"));
+        assert!(dot.contains("\"11B\" -> \"11Z\" [label=\"R\"];"));
+        assert!(dot.contains("\"22B\" -> \"22C\" [label=\"LR\"];"));
+
+        let json = export(EXAMPLE_TWO).to_json();
+        assert!(json.contains("\"11B\": [\"11Z\", \"XXX\"]"));
+    }
 }
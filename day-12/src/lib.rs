@@ -1,61 +1,72 @@
+use flat::coordinate::Coordinate;
+use flat::grid::{Grid, GridFormatter};
 use std::collections::HashMap;
-use std::iter::repeat;
+use std::iter::repeat_n;
 
 pub fn part_one(input: &str) -> i64 {
-    input
-        .lines()
-        .map(|l| Row::new(l, 1))
-        .map(|mut row| row.arrangements())
-        .sum()
+    count_arrangements(input, 1)
 }
 
 pub fn part_two(input: &str) -> i64 {
+    count_arrangements(input, 5)
+}
+
+pub fn count_arrangements(input: &str, num_folds: usize) -> i64 {
     input
         .lines()
-        .map(|l| Row::new(l, 5))
+        .map(|l| Row::new(l, num_folds))
         .map(|mut row| row.arrangements())
         .sum()
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct Row {
+pub struct Row {
     pattern: Vec<Parts>,
     sections: Vec<usize>,
     memoized: HashMap<(usize, usize), i64>,
 }
 impl Row {
-    fn new(line: &str, num_folds: usize) -> Row {
+    pub fn new(line: &str, num_folds: usize) -> Row {
         let (pattern_base, sections_base) = line.split_once(' ').unwrap();
 
-        let pattern = repeat(pattern_base)
-            .take(num_folds)
+        let pattern = repeat_n(pattern_base, num_folds)
             .collect::<Vec<_>>()
             .join("?");
 
-        let sections = repeat(sections_base)
-            .take(num_folds)
+        let sections = repeat_n(sections_base, num_folds)
             .collect::<Vec<_>>()
             .join(",");
 
-        Row {
-            pattern: pattern
-                .chars()
-                .map(|c| match c {
-                    '.' => Parts::Empty,
-                    '?' => Parts::Wildcard,
-                    '#' => Parts::Number,
-                    _ => panic!("Invalid character in pattern"),
-                })
-                .collect(),
-            sections: sections
+        Row::line(
+            pattern.chars().map(Parts::from_char).collect(),
+            sections
                 .split(',')
+                .filter(|s| !s.is_empty())
                 .map(|s| s.parse::<usize>().unwrap())
                 .collect(),
+        )
+    }
+
+    // a single line of cells and the runs of numbers it has to hold, which is
+    // exactly a row or column of a nonogram. a run of 0 is the nonogram way of
+    // saying the line is empty, so those are dropped
+    pub fn line(pattern: Vec<Parts>, sections: Vec<usize>) -> Row {
+        Row {
+            pattern,
+            sections: sections.into_iter().filter(|size| *size > 0).collect(),
             memoized: HashMap::new(),
         }
     }
 
-    fn arrangements(&mut self) -> i64 {
+    pub fn arrangements(&mut self) -> i64 {
+        // with nothing to place the only arrangement is leaving everything empty
+        if self.sections.is_empty() {
+            return match self.pattern.contains(&Parts::Number) {
+                true => 0,
+                false => 1,
+            };
+        }
+
         (0..self.next_number(0))
             .map(|i| self.arrangements_for(0, i))
             .sum()
@@ -92,13 +103,108 @@ impl Row {
 
         // not the last section, sum all the ways we can insert the next section, we'll move
         // our cursor over one to account for the inserted section
-        let start_index = pattern_index + section_size + 1;
-        let end_index = self.next_number(start_index);
+        let (start_index, end_index) = self.following(section_index, pattern_index);
         (start_index..end_index)
             .map(|i| self.arrangements_for(section_index + 1, i))
             .sum()
     }
 
+    // every arrangement one at a time, only ever stepping into positions the
+    // memoized counts say lead somewhere, so each one is found without backtracking
+    pub fn enumerate(&mut self) -> Arrangements<'_> {
+        let done = self.arrangements() == 0;
+        Arrangements {
+            row: self,
+            starts: Vec::new(),
+            started: false,
+            done,
+        }
+    }
+
+    // the cells that are the same across every arrangement, with the rest left
+    // as wildcards, none if there's no arrangement at all
+    pub fn forced(&mut self) -> Option<Vec<Parts>> {
+        let total = self.arrangements();
+        if total == 0 {
+            return None;
+        }
+
+        let mut covered = vec![0; self.pattern.len()];
+        if !self.sections.is_empty() {
+            let before = self.placements_before();
+            for (section_index, section_size) in self.sections.clone().into_iter().enumerate() {
+                for pattern_index in 0..self.pattern.len() {
+                    let before = before[section_index][pattern_index];
+                    if before == 0 {
+                        continue;
+                    }
+
+                    let ways = before * self.arrangements_for(section_index, pattern_index);
+                    for cell in &mut covered[pattern_index..pattern_index + section_size] {
+                        *cell += ways;
+                    }
+                }
+            }
+        }
+
+        Some(
+            covered
+                .into_iter()
+                .map(|count| match count {
+                    0 => Parts::Empty,
+                    c if c == total => Parts::Number,
+                    _ => Parts::Wildcard,
+                })
+                .collect(),
+        )
+    }
+
+    // the number of ways to place every section ahead of the given one so that
+    // it can start at the given index, the mirror of arrangements_for
+    fn placements_before(&self) -> Vec<Vec<i64>> {
+        let length = self.pattern.len();
+        let mut before = vec![vec![0; length]; self.sections.len()];
+
+        for (pattern_index, ways) in before[0].iter_mut().enumerate() {
+            if self.pattern[..pattern_index].contains(&Parts::Number) {
+                break;
+            }
+            if self.can_insert(0, pattern_index) {
+                *ways = 1;
+            }
+        }
+
+        for section_index in 1..self.sections.len() {
+            let previous_size = self.sections[section_index - 1];
+            for pattern_index in 0..length {
+                if !self.can_insert(section_index, pattern_index) {
+                    continue;
+                }
+
+                // walk back over the gap, which can't hold any forced numbers
+                let mut ways = 0;
+                let mut gap_start = pattern_index;
+                while gap_start > previous_size {
+                    gap_start -= 1;
+                    ways += before[section_index - 1][gap_start - previous_size];
+                    if self.pattern[gap_start] == Parts::Number {
+                        break;
+                    }
+                }
+                before[section_index][pattern_index] = ways;
+            }
+        }
+
+        before
+    }
+
+    // the start of the section following the one placed at pattern_index, and
+    // the end of the range it may start within
+    fn following(&self, section_index: usize, pattern_index: usize) -> (usize, usize) {
+        let start_index = pattern_index + self.sections[section_index] + 1;
+        (start_index, self.next_number(start_index))
+    }
+
     // when searching we can only consider elements up to the next guaranteed number
     // position, this will return that, or the end of the pattern if there are no more
     fn next_number(&self, offset: usize) -> usize {
@@ -128,12 +234,216 @@ impl Row {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Parts {
+pub struct Arrangements<'a> {
+    row: &'a mut Row,
+    starts: Vec<usize>,
+    started: bool,
+    done: bool,
+}
+impl Arrangements<'_> {
+    // the range the next section may start within, given those placed so far
+    fn candidates(&self) -> (usize, usize) {
+        match self.starts.last() {
+            Some(start) => self.row.following(self.starts.len() - 1, *start),
+            None => (0, self.row.next_number(0)),
+        }
+    }
+
+    // places the next section at the first position from which the rest can
+    // still be completed, moving the section before it along if there's none
+    fn place(&mut self, mut from: usize) -> bool {
+        loop {
+            let (start, end) = self.candidates();
+            let section_index = self.starts.len();
+            if let Some(i) =
+                (from.max(start)..end).find(|i| self.row.arrangements_for(section_index, *i) > 0)
+            {
+                self.starts.push(i);
+                return true;
+            }
+
+            match self.starts.pop() {
+                Some(previous) => from = previous + 1,
+                None => return false,
+            }
+        }
+    }
+
+    fn render(&self) -> Vec<Parts> {
+        let mut cells = vec![Parts::Empty; self.row.pattern.len()];
+        for (start, size) in self.starts.iter().zip(&self.row.sections) {
+            cells[*start..start + size].fill(Parts::Number);
+        }
+        cells
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<Parts>;
+
+    fn next(&mut self) -> Option<Vec<Parts>> {
+        if self.done {
+            return None;
+        }
+
+        // after the first arrangement we move on from the last section placed,
+        // any sections after the one that moved are then placed from scratch
+        if self.started {
+            let moved = match self.starts.pop() {
+                Some(last) => self.place(last + 1),
+                None => false,
+            };
+            if !moved {
+                self.done = true;
+                return None;
+            }
+        }
+        self.started = true;
+
+        while self.starts.len() < self.row.sections.len() {
+            self.place(0);
+        }
+
+        Some(self.render())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Parts {
     Empty,
     Wildcard,
     Number,
 }
+impl Parts {
+    pub fn from_char(c: char) -> Parts {
+        match c {
+            '.' => Parts::Empty,
+            '?' => Parts::Wildcard,
+            '#' => Parts::Number,
+            _ => panic!("Invalid character in pattern"),
+        }
+    }
+
+    pub fn char(&self) -> char {
+        match self {
+            Parts::Empty => '.',
+            Parts::Wildcard => '?',
+            Parts::Number => '#',
+        }
+    }
+}
+
+// a picross puzzle, every row and column is a line of springs with its own
+// runs to hold, solved by deducing what we can from each line in turn and
+// guessing a cell whenever the deductions dry up
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+impl Nonogram {
+    // runs of 0 are dropped, so a clue of 0 and no clue are the same puzzle
+    pub fn new(rows: Vec<Vec<usize>>, columns: Vec<Vec<usize>>) -> Nonogram {
+        let clues = |lines: Vec<Vec<usize>>| -> Vec<Vec<usize>> {
+            lines
+                .into_iter()
+                .map(|line| line.into_iter().filter(|size| *size > 0).collect())
+                .collect()
+        };
+        Nonogram {
+            rows: clues(rows),
+            columns: clues(columns),
+        }
+    }
+
+    // the row clues, a blank line, then the column clues, one comma separated
+    // list per line with 0 for a line that's empty
+    pub fn parse(input: &str) -> Nonogram {
+        let (rows, columns) = input.split_once("\n\n").unwrap();
+        let clues = |block: &str| -> Vec<Vec<usize>> {
+            block
+                .lines()
+                .map(|line| {
+                    line.split(',')
+                        .map(|s| s.trim().parse::<usize>().unwrap())
+                        .collect()
+                })
+                .collect()
+        };
+        Nonogram::new(clues(rows), clues(columns))
+    }
+
+    // the filled cells as # and the rest as ., none if the clues contradict
+    pub fn solve(&self) -> Option<Grid> {
+        let cells = vec![vec![Parts::Wildcard; self.columns.len()]; self.rows.len()];
+        let cells = self.search(cells)?;
+
+        let mut grid = Grid::new();
+        for (y, row) in cells.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                grid.insert(Coordinate(x as i32, y as i32), cell.char());
+            }
+        }
+        Some(grid)
+    }
+
+    fn search(&self, mut cells: Vec<Vec<Parts>>) -> Option<Vec<Vec<Parts>>> {
+        if !self.propagate(&mut cells) {
+            return None;
+        }
+
+        let unknown = cells.iter().enumerate().find_map(|(y, row)| {
+            row.iter()
+                .position(|cell| *cell == Parts::Wildcard)
+                .map(|x| (x, y))
+        });
+        let Some((x, y)) = unknown else {
+            return Some(cells);
+        };
+
+        [Parts::Number, Parts::Empty].into_iter().find_map(|guess| {
+            let mut cells = cells.clone();
+            cells[y][x] = guess;
+            self.search(cells)
+        })
+    }
+
+    // fills in every cell forced by its row or column until nothing changes,
+    // false if some line no longer has any arrangement
+    fn propagate(&self, cells: &mut [Vec<Parts>]) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for (y, clue) in self.rows.iter().enumerate() {
+                let Some(forced) = Row::line(cells[y].clone(), clue.clone()).forced() else {
+                    return false;
+                };
+                changed |= forced != cells[y];
+                cells[y] = forced;
+            }
+
+            for (x, clue) in self.columns.iter().enumerate() {
+                let column = cells.iter().map(|row| row[x]).collect();
+                let Some(forced) = Row::line(column, clue.clone()).forced() else {
+                    return false;
+                };
+                for (row, cell) in cells.iter_mut().zip(forced) {
+                    changed |= row[x] != cell;
+                    row[x] = cell;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn format(grid: &Grid) -> String {
+        grid.format(GridFormatter::new(
+            '?',
+            vec![('#', "#".to_string()), ('.', ".".to_string())],
+        ))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -161,4 +471,75 @@ mod tests {
         assert_eq!(525152, part_two(EXAMPLE));
         assert_eq!(29826669191291, part_two(INPUT));
     }
+
+    #[test]
+    fn solves_lines() {
+        let render = |cells: &[Parts]| cells.iter().map(|c| c.char()).collect::<String>();
+
+        let mut row = Row::new("?###???????? 3,2,1", 1);
+        let arrangements: Vec<_> = row.enumerate().map(|a| render(&a)).collect();
+        assert_eq!(10, arrangements.len());
+        assert_eq!(".###.##.#...", arrangements[0]);
+        assert_eq!(".###....##.#", arrangements[9]);
+        assert_eq!(
+            Some(".###.???????".to_string()),
+            row.forced().map(|f| render(&f))
+        );
+
+        for line in EXAMPLE.lines() {
+            let mut row = Row::new(line, 2);
+            let expected = row.arrangements();
+            assert_eq!(expected as usize, row.enumerate().count());
+        }
+
+        // the classic nonogram overlap, a run of 8 in 10 cells pins the middle 6
+        let mut row = Row::line(vec![Parts::Wildcard; 10], vec![8]);
+        assert_eq!("??######??", render(&row.forced().unwrap()));
+        assert_eq!(
+            "..........",
+            render(
+                &Row::line(vec![Parts::Wildcard; 10], vec![])
+                    .forced()
+                    .unwrap()
+            )
+        );
+        assert_eq!(None, Row::new("#.? 2", 1).forced());
+
+        // a clue of 0 is the same as no clue at all
+        assert_eq!(
+            1,
+            Row::line(vec![Parts::Wildcard; 2], vec![0]).arrangements()
+        );
+        assert_eq!(
+            Some(vec![Parts::Empty; 2]),
+            Row::line(vec![Parts::Wildcard; 2], vec![0]).forced()
+        );
+        assert_eq!(
+            3,
+            Row::line(vec![Parts::Wildcard; 3], vec![0, 1, 0]).arrangements()
+        );
+        assert_eq!(1, count_arrangements("???.### 1,1,3", 7));
+    }
+
+    #[test]
+    fn solves_nonograms() {
+        // a small house, filled in from its rows and columns alone
+        let house = Nonogram::parse("1\n3\n5\n1,1\n1,1\n\n1\n4\n3\n4\n1");
+        assert_eq!(
+            "..#..\n.###.\n#####\n.#.#.\n.#.#.",
+            Nonogram::format(&house.solve().unwrap())
+        );
+
+        // two filled cells on a diagonal, the lines alone can't tell which
+        // diagonal so this needs a guess
+        let diagonal = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        assert_eq!("#.\n.#", Nonogram::format(&diagonal.solve().unwrap()));
+
+        let impossible = Nonogram::new(vec![vec![2], vec![]], vec![vec![1], vec![]]);
+        assert_eq!(None, impossible.solve().map(|grid| Nonogram::format(&grid)));
+        let zeroes = Nonogram::new(vec![vec![2], vec![0]], vec![vec![1], vec![0]]);
+        assert_eq!(impossible, zeroes);
+        let parsed = Nonogram::parse("1\n0\n\n0\n1");
+        assert_eq!(".#\n..", Nonogram::format(&parsed.solve().unwrap()));
+    }
 }
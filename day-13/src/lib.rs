@@ -3,50 +3,142 @@ use flat::grid::Grid;
 use std::str::FromStr;
 
 pub fn part_one(input: &str) -> i32 {
-    input
-        .split("\n\n")
-        .map(|grid_str| Grid::from_str(grid_str).unwrap())
-        .map(|grid| score(grid, false))
-        .sum()
+    summarize(input, 0).unwrap()
 }
 
 pub fn part_two(input: &str) -> i32 {
+    summarize(input, 1).unwrap()
+}
+
+// the puzzle's summary, each pattern scored by its first reflection that
+// needs exactly the given number of smudges fixed
+pub fn summarize(input: &str, smudges: usize) -> Result<i32, String> {
+    parse(input)
+        .iter()
+        .enumerate()
+        .map(|(index, grid)| {
+            score(grid, smudges).map_err(|e| format!("pattern {}: {}", index + 1, e))
+        })
+        .sum()
+}
+
+pub fn parse(input: &str) -> Vec<Grid> {
     input
         .split("\n\n")
         .map(|grid_str| Grid::from_str(grid_str).unwrap())
-        .map(|grid| score(grid, true))
-        .sum()
+        .collect()
+}
+
+pub fn score(grid: &Grid, smudges: usize) -> Result<i32, String> {
+    reflections(grid, smudges)
+        .first()
+        .map(|axis| axis.score())
+        .ok_or_else(|| format!("no reflection with {} smudges", smudges))
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Orientation {
+    // a vertical line between columns, mirroring left and right
+    Vertical,
+    // a horizontal line between rows, mirroring top and bottom
+    Horizontal,
 }
 
-fn score(grid: Grid, smudge: bool) -> i32 {
-    match reflection_index(&grid, smudge) {
-        Some(col) => col,
-        None => match reflection_index(&grid.rotate_right(), smudge) {
-            Some(row) => row * 100,
-            _ => panic!("no reflection found in grid"),
-        },
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Axis {
+    pub orientation: Orientation,
+    // the number of columns left of, or rows above, the line
+    pub index: i32,
+    // how many cells don't match their reflection
+    pub differences: usize,
+}
+impl Axis {
+    pub fn score(&self) -> i32 {
+        match self.orientation {
+            Orientation::Vertical => self.index,
+            Orientation::Horizontal => self.index * 100,
+        }
     }
 }
 
-fn reflection_index(grid: &Grid, smudge: bool) -> Option<i32> {
-    (1..=grid.bounds.x.max).find(|x| {
-        let reflection_size = *x.min(&(grid.bounds.x.max - x + 1));
-        let differences: usize = (0..=grid.bounds.y.max)
-            .map(|y| {
-                (1..=reflection_size)
-                    .filter(|i| {
-                        let left = grid.get(&Coordinate(*x + i - 1, y));
-                        let right = grid.get(&Coordinate(*x - i, y));
-                        left != right
-                    })
-                    .count()
-            })
-            .sum();
-
-        match smudge {
-            true => differences == 1,
-            false => differences == 0,
+// every line the pattern could be folded along, vertical ones first
+pub fn axes(grid: &Grid) -> Vec<Axis> {
+    let transposed = grid.rotate_right();
+    let vertical = axes_of(grid, Orientation::Vertical);
+    let horizontal = axes_of(&transposed, Orientation::Horizontal);
+    vertical.chain(horizontal).collect()
+}
+
+// the axes that would be perfect reflections once the given number of
+// smudges are cleaned up
+pub fn reflections(grid: &Grid, smudges: usize) -> Vec<Axis> {
+    axes(grid)
+        .into_iter()
+        .filter(|axis| axis.differences == smudges)
+        .collect()
+}
+
+// a cell that doesn't match its reflection across an axis, flipping either
+// of them fixes it, we treat the one nearest the top left as the smudge
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Smudge {
+    pub cell: Coordinate,
+    pub reflection: Coordinate,
+}
+
+// the cells that need flipping for the axis to become a perfect reflection
+pub fn smudges(grid: &Grid, axis: &Axis) -> Vec<Smudge> {
+    match axis.orientation {
+        Orientation::Vertical => mismatches(grid, axis.index)
+            .map(|(cell, reflection)| Smudge { cell, reflection })
+            .collect(),
+        Orientation::Horizontal => {
+            let transposed = grid.rotate_right();
+            let transpose = |c: Coordinate| Coordinate(c.1, c.0);
+            mismatches(&transposed, axis.index)
+                .map(|(cell, reflection)| Smudge {
+                    cell: transpose(cell),
+                    reflection: transpose(reflection),
+                })
+                .collect()
         }
+    }
+}
+
+// a copy of the grid with every smudge for the axis flipped
+pub fn repair(grid: &Grid, axis: &Axis) -> Grid {
+    let mut repaired = Grid::new();
+    for (coord, c) in &grid.points {
+        repaired.insert(*coord, *c);
+    }
+
+    for smudge in smudges(grid, axis) {
+        let flipped = match grid.get(&smudge.reflection) {
+            Some(c) => *c,
+            None => panic!("no cell at {:?}", smudge.reflection),
+        };
+        repaired.insert(smudge.cell, flipped);
+    }
+    repaired
+}
+
+// rows are handled by flipping the grid on its diagonal, so we only ever
+// look for vertical lines here
+fn axes_of(grid: &Grid, orientation: Orientation) -> impl Iterator<Item = Axis> + '_ {
+    (1..=grid.bounds.x.max).map(move |x| Axis {
+        orientation,
+        index: x,
+        differences: mismatches(grid, x).count(),
+    })
+}
+
+// each pair of cells either side of the line at x that don't match
+fn mismatches(grid: &Grid, x: i32) -> impl Iterator<Item = (Coordinate, Coordinate)> + '_ {
+    let reflection_size = x.min(grid.bounds.x.max - x + 1);
+    (0..=grid.bounds.y.max).flat_map(move |y| {
+        (1..=reflection_size)
+            .map(move |i| (Coordinate(x - i, y), Coordinate(x + i - 1, y)))
+            .filter(|(left, right)| grid.get(left) != grid.get(right))
     })
 }
 
@@ -68,4 +160,71 @@ mod tests {
         assert_eq!(400, part_two(EXAMPLE));
         assert_eq!(39359, part_two(INPUT));
     }
+
+    #[test]
+    fn reports_every_axis() {
+        let grids = parse(EXAMPLE);
+
+        let axes = axes(&grids[0]);
+        assert_eq!(8 + 6, axes.len());
+        assert_eq!(
+            Some(&Axis {
+                orientation: Orientation::Vertical,
+                index: 5,
+                differences: 0
+            }),
+            axes.iter().find(|axis| axis.differences == 0)
+        );
+        assert_eq!(
+            vec![Axis {
+                orientation: Orientation::Horizontal,
+                index: 3,
+                differences: 1
+            }],
+            reflections(&grids[0], 1)
+        );
+
+        assert_eq!(Ok(400), score(&grids[1], 0));
+        assert_eq!(Ok(100), score(&grids[1], 1));
+        assert!(score(&grids[0], 30).is_err());
+        assert_eq!(
+            Err("pattern 1: no reflection with 30 smudges".to_string()),
+            summarize(EXAMPLE, 30)
+        );
+    }
+
+    #[test]
+    fn locates_smudges() {
+        let grids = parse(EXAMPLE);
+
+        let axis = reflections(&grids[0], 1)[0];
+        assert_eq!(
+            vec![Smudge {
+                cell: Coordinate(0, 0),
+                reflection: Coordinate(0, 5)
+            }],
+            smudges(&grids[0], &axis)
+        );
+
+        let repaired = repair(&grids[0], &axis);
+        assert_eq!(Some(&'.'), repaired.get(&Coordinate(0, 0)));
+        // the old vertical line still holds, the repair adds the horizontal one
+        assert_eq!(
+            vec![5, 300],
+            reflections(&repaired, 0)
+                .iter()
+                .map(|axis| axis.score())
+                .collect::<Vec<_>>()
+        );
+
+        let axis = reflections(&grids[1], 1)[0];
+        assert_eq!(
+            vec![Smudge {
+                cell: Coordinate(4, 0),
+                reflection: Coordinate(4, 1)
+            }],
+            smudges(&grids[1], &axis)
+        );
+        assert_eq!(Ok(100), score(&repair(&grids[1], &axis), 0));
+    }
 }
@@ -1,97 +1,160 @@
 use flat::coordinate::Coordinate;
-use flat::coordinate::Direction::{East, North, South, West};
 use flat::grid::Grid;
 use std::collections::HashMap;
 use std::str::FromStr;
 
 const ROCK: char = 'O';
 const WALL: char = '#';
-const CYCLE_COUNT: i32 = 1_000_000_000;
+const EMPTY: char = '.';
+const CYCLE_COUNT: usize = 1_000_000_000;
 
-enum Tilt {
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Tilt {
     North,
     South,
     East,
     West,
 }
 
+pub const SPIN_CYCLE: [Tilt; 4] = [Tilt::North, Tilt::West, Tilt::South, Tilt::East];
+
 pub fn part_one(input: &str) -> i32 {
-    score(&tilt(parse(input), Tilt::North))
+    load(&tilt(parse(input), Tilt::North), Tilt::North)
 }
 
 pub fn part_two(input: &str) -> i32 {
-    let mut grid = parse(input);
+    spin_load(input, &SPIN_CYCLE, CYCLE_COUNT, Tilt::North)
+}
+
+pub fn spin_load(input: &str, tilts: &[Tilt], cycles: usize, edge: Tilt) -> i32 {
+    load(&spin(parse(input), tilts, cycles), edge)
+}
+
+// runs the tilts in order, the given number of times over, once the platform
+// comes back around to a layout it's already been in we know it'll keep
+// repeating that loop, so we can skip ahead over as many loops as fit
+pub fn spin(grid: Grid, tilts: &[Tilt], cycles: usize) -> Grid {
+    let mut platform = Platform::from_grid(&grid);
     let mut current_cycle = 0;
-    let mut cycle_cache: HashMap<u64, i32> = HashMap::new();
+    let mut cycle_cache: HashMap<Vec<char>, usize> = HashMap::new();
 
-    while current_cycle < CYCLE_COUNT {
-        grid = tilt(grid, Tilt::North);
-        grid = tilt(grid, Tilt::West);
-        grid = tilt(grid, Tilt::South);
-        grid = tilt(grid, Tilt::East);
+    while current_cycle < cycles {
+        for direction in tilts {
+            platform.tilt(*direction);
+        }
         current_cycle += 1;
 
-        let cycle_hash = grid.hash();
-
-        if let Some(cycle_at) = cycle_cache.get(&cycle_hash) {
+        if let Some(cycle_at) = cycle_cache.get(&platform.cells) {
             let loop_size = current_cycle - cycle_at;
-            let remaining = CYCLE_COUNT - current_cycle;
+            let remaining = cycles - current_cycle;
             current_cycle += (remaining / loop_size) * loop_size;
         }
 
-        cycle_cache.insert(cycle_hash, current_cycle);
+        cycle_cache.insert(platform.cells.clone(), current_cycle);
     }
 
-    score(&grid)
+    platform.to_grid()
 }
 
-fn parse(input: &str) -> Grid {
+pub fn parse(input: &str) -> Grid {
     let mut grid = Grid::from_str(input).unwrap();
-    grid.remove_char('.');
+    grid.remove_char(EMPTY);
     grid
 }
 
-fn tilt(mut grid: Grid, tilt: Tilt) -> Grid {
-    let (starting_point, side_direction, rock_direction) = match tilt {
-        Tilt::North => (Coordinate(0, 0), East, South),
-        Tilt::South => (Coordinate(0, grid.bounds.y.max), East, North),
-        Tilt::West => (Coordinate(0, 0), South, East),
-        Tilt::East => (Coordinate(grid.bounds.x.max, 0), South, West),
-    };
-
-    let mut side_position = starting_point;
-
-    while grid.in_bounds(&side_position) {
-        let mut fall_to: Option<Coordinate> = None;
-        let mut rock_position = side_position;
+pub fn tilt(grid: Grid, tilt: Tilt) -> Grid {
+    let mut platform = Platform::from_grid(&grid);
+    platform.tilt(tilt);
+    platform.to_grid()
+}
 
-        while grid.in_bounds(&rock_position) {
-            if grid.is_empty(&rock_position) && fall_to.is_none() {
-                fall_to = Some(rock_position);
-            }
+// the grid laid out flat row by row, so tilting is just shuffling chars
+// around a vector rather than moving points in and out of a map
+struct Platform {
+    width: usize,
+    height: usize,
+    cells: Vec<char>,
+}
+impl Platform {
+    fn from_grid(grid: &Grid) -> Platform {
+        let width = grid.bounds.width() as usize;
+        let height = grid.bounds.height() as usize;
+        let cells = (0..height)
+            .flat_map(|y| {
+                (0..width)
+                    .map(move |x| grid.get_with_default(&Coordinate(x as i32, y as i32), EMPTY))
+            })
+            .collect();
+
+        Platform {
+            width,
+            height,
+            cells,
+        }
+    }
 
-            if grid.is_equal(&rock_position, &WALL) {
-                fall_to = None;
-            }
+    fn to_grid(&self) -> Grid {
+        let mut grid = Grid::new();
+        for (index, c) in self.cells.iter().enumerate() {
+            let coord = Coordinate((index % self.width) as i32, (index / self.width) as i32);
+            grid.insert(coord, *c);
+        }
+        grid.remove_char(EMPTY);
+        grid
+    }
 
-            if grid.is_equal(&rock_position, &ROCK) && fall_to.is_some() {
-                grid.move_point(rock_position, fall_to.unwrap());
-                fall_to = Some(fall_to.unwrap() + rock_direction);
+    // each line runs from the edge the rocks roll towards, the walls split it
+    // into segments and every rock in a segment piles up at its start
+    fn tilt(&mut self, tilt: Tilt) {
+        let (lines, length) = match tilt {
+            Tilt::North | Tilt::South => (self.width, self.height),
+            Tilt::East | Tilt::West => (self.height, self.width),
+        };
+
+        for line in 0..lines {
+            let (start, step) = match tilt {
+                Tilt::North => (line as isize, self.width as isize),
+                Tilt::South => (
+                    ((self.height - 1) * self.width + line) as isize,
+                    -(self.width as isize),
+                ),
+                Tilt::West => ((line * self.width) as isize, 1),
+                Tilt::East => ((line * self.width + self.width - 1) as isize, -1),
+            };
+            let index = |i: usize| (start + i as isize * step) as usize;
+
+            let mut segment_start = 0;
+            let mut rocks = 0;
+            for i in 0..=length {
+                if i < length && self.cells[index(i)] != WALL {
+                    rocks += (self.cells[index(i)] == ROCK) as usize;
+                    continue;
+                }
+
+                for j in segment_start..i {
+                    self.cells[index(j)] = match j - segment_start < rocks {
+                        true => ROCK,
+                        false => EMPTY,
+                    };
+                }
+                segment_start = i + 1;
+                rocks = 0;
             }
-
-            rock_position = rock_position + rock_direction;
         }
-
-        side_position = side_position + side_direction;
     }
-
-    grid
 }
 
-fn score(grid: &Grid) -> i32 {
+// each rock weighs more the further it sits from the edge opposite the one
+// given, a rock right up against the given edge counts for the full length
+pub fn load(grid: &Grid, edge: Tilt) -> i32 {
     grid.find_all(&ROCK)
         .iter()
-        .map(|coord| grid.bounds.y.max - coord.1 + 1)
+        .map(|coord| match edge {
+            Tilt::North => grid.bounds.y.max - coord.1 + 1,
+            Tilt::South => coord.1 - grid.bounds.y.min + 1,
+            Tilt::West => grid.bounds.x.max - coord.0 + 1,
+            Tilt::East => coord.0 - grid.bounds.x.min + 1,
+        })
         .sum()
 }
 
@@ -111,8 +174,37 @@ mod tests {
     #[test]
     fn part_two_works() {
         assert_eq!(64, part_two(EXAMPLE));
-        // takes a ~2 seconds to run, too slow to include in normal test suite,
-        // in release mode takes 0.01s
-        // assert_eq!(88680, part_two(INPUT));
+        assert_eq!(88680, part_two(INPUT));
+    }
+
+    #[test]
+    fn spins_any_sequence() {
+        assert_eq!(
+            64,
+            spin_load(EXAMPLE, &SPIN_CYCLE, CYCLE_COUNT, Tilt::North)
+        );
+
+        // worked by hand: south drops both rocks to the bottom row, then east
+        // slides them into its last two cells, x = 1 and x = 2
+        let small = "O.#\n.O.\n...";
+        let south_east = [Tilt::South, Tilt::East];
+        assert_eq!(2 + 1, spin_load(small, &south_east, 1, Tilt::West));
+        assert_eq!(1 + 1, spin_load(small, &south_east, 1, Tilt::North));
+        assert_eq!(2 + 3, spin_load(small, &south_east, 1_000, Tilt::East));
+
+        // north pushes the middle rock up next to the one already on the top row
+        assert_eq!(3 + 3, spin_load(small, &[Tilt::North], 5, Tilt::North));
+
+        // tilting back and forth settles after the first pass
+        let there_and_back = [Tilt::East, Tilt::West];
+        assert_eq!(
+            spin_load(EXAMPLE, &there_and_back, 1, Tilt::West),
+            spin_load(EXAMPLE, &there_and_back, 1_000, Tilt::West)
+        );
+
+        // measured from opposite edges each of the 18 rocks weighs the width + 1
+        let grid = tilt(parse(EXAMPLE), Tilt::West);
+        assert_eq!(18 * 11, load(&grid, Tilt::West) + load(&grid, Tilt::East));
+        assert_eq!(136, load(&tilt(parse(EXAMPLE), Tilt::North), Tilt::North));
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+use std::fmt;
 use std::iter::repeat_with;
 
 pub fn part_one(input: &str) -> usize {
@@ -5,58 +7,243 @@ pub fn part_one(input: &str) -> usize {
 }
 
 pub fn part_two(input: &str) -> usize {
-    let mut boxes: Vec<_> = repeat_with(Box::default).take(256).collect();
-
-    input.trim().split(',').for_each(|instruction| {
-        let (label, fl) = instruction.split_once(|c| c == '-' || c == '=').unwrap();
-        let box_idx = hash(label);
-
-        match fl.is_empty() {
-            true => boxes[box_idx].lenses.retain(|lens| lens.label != label),
-            false => match boxes[box_idx]
-                .lenses
-                .iter_mut()
-                .find(|lens| lens.label == label)
-            {
-                Some(lens) => lens.focal_length = fl.parse().unwrap(),
-                None => {
-                    boxes[box_idx].lenses.push(Lens {
-                        label: label.to_string(),
-                        focal_length: fl.parse().unwrap(),
-                    });
-                }
-            },
-        }
-    });
-
-    boxes
-        .iter()
-        .enumerate()
-        .map(|(box_idx, light_box)| {
-            light_box
-                .lenses
-                .iter()
-                .enumerate()
-                .map(|(lens_idx, lens)| (box_idx + 1) * (lens_idx + 1) * lens.focal_length)
-                .sum::<usize>()
+    let mut boxes = LensBoxes::new();
+    for instruction in parse(input) {
+        boxes.apply(instruction);
+    }
+    boxes.focusing_power()
+}
+
+// the state of the boxes after every instruction, in the same format the
+// puzzle uses for its walkthrough
+pub fn trace(input: &str) -> String {
+    let mut boxes = LensBoxes::new();
+    parse(input)
+        .into_iter()
+        .map(|instruction| {
+            let step = format!("After \"{}\":", instruction);
+            boxes.apply(instruction);
+            format!("{}\n{}", step, boxes)
         })
-        .sum()
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub fn parse(input: &str) -> Vec<Instruction> {
+    input.trim().split(',').map(Instruction::parse).collect()
 }
 
-fn hash(label: &str) -> usize {
+pub fn hash(label: &str) -> usize {
     label
         .chars()
         .fold(0, |acc, c| ((acc + c as usize) * 17) % 256)
 }
 
+// picks the box a label goes in, the result is wrapped to the number of boxes
+// so a hasher doesn't need to know how many there are
+pub trait LabelHasher {
+    fn hash(&self, label: &str) -> usize;
+}
+
+// the puzzle's Holiday ASCII String Helper algorithm
+pub struct Holiday;
+impl LabelHasher for Holiday {
+    fn hash(&self, label: &str) -> usize {
+        hash(label)
+    }
+}
+
+impl<F: Fn(&str) -> usize> LabelHasher for F {
+    fn hash(&self, label: &str) -> usize {
+        self(label)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Instruction {
+    pub label: String,
+    pub operation: Operation,
+}
+impl Instruction {
+    pub fn parse(instruction: &str) -> Instruction {
+        let (label, fl) = instruction.split_once(['-', '=']).unwrap();
+        Instruction {
+            label: label.to_string(),
+            operation: match fl.is_empty() {
+                true => Operation::Remove,
+                false => Operation::Insert(fl.parse().unwrap()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operation {
+            Operation::Remove => write!(f, "{}-", self.label),
+            Operation::Insert(focal_length) => write!(f, "{}={}", self.label, focal_length),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Operation {
+    Remove,
+    Insert(usize),
+}
+
+// the HASHMAP procedure, every instruction applied keeps a copy of the box it
+// changed so it can be rolled back again
+pub struct LensBoxes<H = Holiday> {
+    hasher: H,
+    boxes: Vec<Box>,
+    history: Vec<(Instruction, Vec<Lens>)>,
+}
+impl LensBoxes {
+    pub fn new() -> LensBoxes {
+        LensBoxes::with_hasher(Holiday, 256)
+    }
+}
+
+impl<H: LabelHasher> LensBoxes<H> {
+    pub fn with_hasher(hasher: H, box_count: usize) -> LensBoxes<H> {
+        assert!(box_count > 0, "there must be at least one box");
+        LensBoxes {
+            hasher,
+            boxes: repeat_with(Box::default).take(box_count).collect(),
+            history: Vec::new(),
+        }
+    }
+
+    pub fn box_index(&self, label: &str) -> usize {
+        self.hasher.hash(label) % self.boxes.len()
+    }
+
+    pub fn apply(&mut self, instruction: Instruction) {
+        let box_idx = self.box_index(&instruction.label);
+        let lenses = &mut self.boxes[box_idx].lenses;
+        let previous = lenses.clone();
+
+        match instruction.operation {
+            Operation::Remove => lenses.retain(|lens| lens.label != instruction.label),
+            Operation::Insert(focal_length) => {
+                match lenses
+                    .iter_mut()
+                    .find(|lens| lens.label == instruction.label)
+                {
+                    Some(lens) => lens.focal_length = focal_length,
+                    None => lenses.push(Lens {
+                        label: instruction.label.clone(),
+                        focal_length,
+                    }),
+                }
+            }
+        }
+
+        self.history.push((instruction, previous));
+    }
+
+    // rolls back the last instruction applied, handing it back
+    pub fn undo(&mut self) -> Option<Instruction> {
+        let (instruction, previous) = self.history.pop()?;
+        let box_idx = self.box_index(&instruction.label);
+        self.boxes[box_idx].lenses = previous;
+        Some(instruction)
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &Instruction> {
+        self.history.iter().map(|(instruction, _)| instruction)
+    }
+
+    pub fn lenses(&self, box_idx: usize) -> &[Lens] {
+        &self.boxes[box_idx].lenses
+    }
+
+    pub fn focusing_power(&self) -> usize {
+        self.boxes
+            .iter()
+            .enumerate()
+            .map(|(box_idx, light_box)| {
+                light_box
+                    .lenses
+                    .iter()
+                    .enumerate()
+                    .map(|(lens_idx, lens)| (box_idx + 1) * (lens_idx + 1) * lens.focal_length)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    // how each box is being used, the labels are every distinct label sent to
+    // the box by the instructions applied so far, whether or not it's still there
+    pub fn stats(&self) -> Vec<BoxStats> {
+        let mut labels: Vec<HashSet<&str>> = vec![HashSet::new(); self.boxes.len()];
+        for (instruction, _) in &self.history {
+            labels[self.box_index(&instruction.label)].insert(&instruction.label);
+        }
+
+        self.boxes
+            .iter()
+            .zip(labels)
+            .enumerate()
+            .map(|(index, (light_box, labels))| BoxStats {
+                index,
+                lenses: light_box.lenses.len(),
+                labels: labels.len(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BoxStats {
+    pub index: usize,
+    pub lenses: usize,
+    pub labels: usize,
+}
+impl BoxStats {
+    // every label past the first that landed in the same box
+    pub fn collisions(&self) -> usize {
+        self.labels.saturating_sub(1)
+    }
+}
+
+impl Default for LensBoxes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// only the boxes holding lenses are shown, e.g. Box 0: [rn 1] [cm 2]
+impl<H> fmt::Display for LensBoxes<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<_> = self
+            .boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, light_box)| !light_box.lenses.is_empty())
+            .map(|(box_idx, light_box)| {
+                let lenses: Vec<_> = light_box
+                    .lenses
+                    .iter()
+                    .map(|lens| format!("[{} {}]", lens.label, lens.focal_length))
+                    .collect();
+                format!("Box {}: {}", box_idx, lenses.join(" "))
+            })
+            .collect();
+        f.write_str(&lines.join("\n"))
+    }
+}
+
 #[derive(Default)]
 struct Box {
     lenses: Vec<Lens>,
 }
 
-struct Lens {
-    label: String,
-    focal_length: usize,
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Lens {
+    pub label: String,
+    pub focal_length: usize,
 }
 
 #[cfg(test)]
@@ -77,4 +264,86 @@ mod tests {
         assert_eq!(145, part_two(EXAMPLE));
         assert_eq!(241094, part_two(INPUT));
     }
+
+    #[test]
+    fn steps_through_instructions() {
+        let trace = trace(EXAMPLE);
+        let steps: Vec<_> = trace.split("\n\n").collect();
+        assert_eq!(11, steps.len());
+        assert_eq!("After \"rn=1\":\nBox 0: [rn 1]", steps[0]);
+        assert_eq!(
+            "After \"ot=7\":\nBox 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]",
+            steps[10]
+        );
+
+        let mut boxes = LensBoxes::new();
+        for instruction in parse(EXAMPLE) {
+            boxes.apply(instruction);
+        }
+        assert_eq!(145, boxes.focusing_power());
+        assert_eq!(11, boxes.history().count());
+
+        // ot=7 replaced the 9 from ot=9, undoing it brings that back
+        assert_eq!(Some(Instruction::parse("ot=7")), boxes.undo());
+        assert_eq!(9, boxes.lenses(3)[0].focal_length);
+
+        // undoing pc- puts pc back where it was
+        assert_eq!(Some(Instruction::parse("pc=6")), boxes.undo());
+        assert_eq!(Some(Instruction::parse("pc-")), boxes.undo());
+        assert_eq!(
+            vec!["pc", "ot", "ab"],
+            boxes
+                .lenses(3)
+                .iter()
+                .map(|lens| lens.label.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        while boxes.undo().is_some() {}
+        assert_eq!("", boxes.to_string());
+    }
+
+    #[test]
+    fn swaps_hashers() {
+        let mut boxes = LensBoxes::new();
+        for instruction in parse(EXAMPLE) {
+            boxes.apply(instruction);
+        }
+        let stats = boxes.stats();
+        assert_eq!(256, stats.len());
+        // rn and cm share box 0, qp is alone in box 1 and gone again
+        assert_eq!(
+            BoxStats {
+                index: 0,
+                lenses: 2,
+                labels: 2
+            },
+            stats[0]
+        );
+        assert_eq!(0, stats[1].lenses);
+        assert_eq!(0, stats[1].collisions());
+        assert_eq!(2, stats[3].collisions());
+
+        // every example label is two letters long, so hashing on length puts
+        // them all in the same box
+        let mut by_length = LensBoxes::with_hasher(|label: &str| label.len(), 4);
+        for instruction in parse(EXAMPLE) {
+            by_length.apply(instruction);
+        }
+        assert_eq!(
+            "Box 2: [rn 1] [cm 2] [ot 7] [ab 5] [pc 6]",
+            by_length.to_string()
+        );
+        assert_eq!(5, by_length.stats()[2].collisions());
+        assert_eq!(
+            3 * (1 + 2 * 2 + 3 * 7 + 4 * 5 + 5 * 6),
+            by_length.focusing_power()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "there must be at least one box")]
+    fn needs_a_box() {
+        LensBoxes::with_hasher(Holiday, 0);
+    }
 }
@@ -3,80 +3,378 @@ use flat::{
         Coordinate,
         Direction::{self, East, North, South, West},
     },
-    grid::Grid,
+    grid::{Bounds, Grid},
 };
+use graph::graph::Graph;
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub fn part_one(input: &str) -> usize {
-    count_energized(&Grid::parse(input), Coordinate(0, 0), East)
+    BeamGraph::new(&Grid::parse(input)).energized(Coordinate(0, 0), East)
 }
 
 pub fn part_two(input: &str) -> usize {
     let grid = Grid::parse(input);
-    let bounds = grid.bounds;
+    let beams = BeamGraph::new(&grid);
 
-    (bounds.x.min..=bounds.x.max)
-        .map(|x| ((x, bounds.y.min), South))
-        .chain((bounds.x.min..=bounds.x.max).map(|x| ((x, bounds.y.max), North)))
-        .chain((bounds.y.min..=bounds.y.max).map(|y| ((bounds.x.min, y), East)))
-        .chain((bounds.y.min..=bounds.y.max).map(|y| ((bounds.x.max, y), West)))
-        .map(|(c, dir)| count_energized(&grid, Coordinate(c.0, c.1), dir))
+    entry_points(&grid.bounds)
+        .map(|(start, direction)| beams.energized(start, direction))
         .max()
         .unwrap()
 }
 
-fn count_energized(grid: &Grid, starting: Coordinate, direction: Direction) -> usize {
-    let mut energized: HashSet<Laser> = HashSet::new();
-    let mut lasers = vec![Laser::new(starting, direction)];
-    let mut split_lasers: Vec<Laser> = vec![];
-
-    while !lasers.is_empty() {
-        for laser in lasers.iter_mut() {
-            energized.insert(laser.clone());
-
-            laser.direction = match grid.get(&laser.position) {
-                Some('.') => laser.direction,
-                Some('\\') => match laser.direction {
-                    North => West,
-                    South => East,
-                    East => South,
-                    West => North,
-                    _ => panic!("unknown direction {:?}", laser.direction),
-                },
-                Some('/') => match laser.direction {
-                    North => East,
-                    South => West,
-                    East => North,
-                    West => South,
-                    _ => panic!("unknown direction {:?}", laser.direction),
-                },
-                Some('|') => match laser.direction {
-                    East | West => {
-                        split_lasers.push(Laser::new(laser.position + North, North));
-                        South
-                    }
-                    _ => laser.direction,
-                },
-                Some('-') => match laser.direction {
-                    North | South => {
-                        split_lasers.push(Laser::new(laser.position + East, East));
-                        West
-                    }
-                    _ => laser.direction,
-                },
-                _ => panic!("unknown character at {:?}", laser.position),
+// every cell along the edge with the direction pointing into the grid
+fn entry_points(bounds: &Bounds) -> impl Iterator<Item = (Coordinate, Direction)> {
+    let bounds = *bounds;
+    (bounds.x.min..=bounds.x.max)
+        .map(move |x| (Coordinate(x, bounds.y.min), South))
+        .chain((bounds.x.min..=bounds.x.max).map(move |x| (Coordinate(x, bounds.y.max), North)))
+        .chain((bounds.y.min..=bounds.y.max).map(move |y| (Coordinate(bounds.x.min, y), East)))
+        .chain((bounds.y.min..=bounds.y.max).map(move |y| (Coordinate(bounds.x.max, y), West)))
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Element {
+    // beams carry straight on
+    Empty,
+    // beams stop dead
+    Absorber,
+    // beams heading one way are turned another, any direction that isn't
+    // listed passes straight through, so leaving some out makes it one way
+    Mirror(Vec<(Direction, Direction)>),
+    // beams leave in every listed direction, other than straight back the way
+    // they came, so a beam heading along the splitter just passes through
+    Splitter(Vec<Direction>),
+    // beams come out of the other cell holding the same character and carry
+    // on in the direction they were heading
+    Portal,
+}
+impl Element {
+    fn outputs(&self, direction: Direction) -> Vec<Direction> {
+        match self {
+            Element::Empty | Element::Portal => vec![direction],
+            Element::Absorber => vec![],
+            Element::Mirror(turns) => match turns.iter().find(|(from, _)| *from == direction) {
+                Some((_, to)) => vec![*to],
+                None => vec![direction],
+            },
+            Element::Splitter(outputs) => outputs
+                .iter()
+                .filter(|output| **output != direction.invert())
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+pub struct Registry {
+    kinds: HashMap<char, Element>,
+}
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            kinds: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, c: char, element: Element) {
+        self.kinds.insert(c, element);
+    }
+}
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register('.', Element::Empty);
+        registry.register(
+            '/',
+            Element::Mirror(vec![
+                (North, East),
+                (East, North),
+                (South, West),
+                (West, South),
+            ]),
+        );
+        registry.register(
+            '\\',
+            Element::Mirror(vec![
+                (North, West),
+                (West, North),
+                (South, East),
+                (East, South),
+            ]),
+        );
+        registry.register('|', Element::Splitter(vec![North, South]));
+        registry.register('-', Element::Splitter(vec![East, West]));
+        registry
+    }
+}
+
+// the grid with every character looked up in the registry, and each portal
+// paired with its other end
+pub struct Contraption {
+    bounds: Bounds,
+    elements: HashMap<Coordinate, Element>,
+    portals: HashMap<Coordinate, Coordinate>,
+}
+impl Contraption {
+    pub fn new(grid: &Grid, registry: &Registry) -> Result<Contraption, String> {
+        let mut elements = HashMap::new();
+        let mut portal_ends: HashMap<char, Vec<Coordinate>> = HashMap::new();
+
+        // in reading order, so the first problem reported is the first on the page
+        for (coord, c) in grid.points.iter().sorted_by_key(|(c, _)| (c.1, c.0)) {
+            let element = match registry.kinds.get(c) {
+                Some(element) => element.clone(),
+                None => return Err(format!("unknown character {:?} at {}", c, coord)),
             };
+            if element == Element::Portal {
+                portal_ends.entry(*c).or_default().push(*coord);
+            }
+            elements.insert(*coord, element);
+        }
+
+        let mut portals = HashMap::new();
+        for (c, ends) in portal_ends {
+            match ends[..] {
+                [a, b] => {
+                    portals.insert(a, b);
+                    portals.insert(b, a);
+                }
+                _ => return Err(format!("portal {:?} has {} ends, not 2", c, ends.len())),
+            }
+        }
 
-            laser.position = laser.position + laser.direction;
+        Ok(Contraption {
+            bounds: grid.bounds,
+            elements,
+            portals,
+        })
+    }
+
+    // the cell a beam arriving at the position leaves from, which is only
+    // somewhere else for portals, and the directions it leaves in
+    fn outputs(&self, position: Coordinate, direction: Direction) -> (Coordinate, Vec<Direction>) {
+        let element = &self.elements[&position];
+        let from = match element {
+            Element::Portal => self.portals[&position],
+            _ => position,
+        };
+        (from, element.outputs(direction))
+    }
+
+    fn in_bounds(&self, position: &Coordinate) -> bool {
+        self.elements.contains_key(position)
+    }
+
+    // runs every beam cell by cell, slower than the beam graph but handy to
+    // check it against
+    pub fn count_energized(&self, starting: Coordinate, direction: Direction) -> usize {
+        let mut energized: HashSet<Laser> = HashSet::new();
+        let mut cells: HashSet<Coordinate> = HashSet::new();
+        let mut lasers = vec![Laser::new(starting, direction)];
+
+        while let Some(laser) = lasers.pop() {
+            if !self.in_bounds(&laser.position) || !energized.insert(laser.clone()) {
+                continue;
+            }
+
+            let (from, directions) = self.outputs(laser.position, laser.direction);
+            cells.insert(laser.position);
+            cells.insert(from);
+            lasers.extend(directions.into_iter().map(|d| Laser::new(from + d, d)));
         }
 
-        lasers.append(&mut split_lasers);
-        lasers.retain(|l| grid.in_bounds(&l.position));
-        lasers.retain(|l| !energized.contains(l));
+        cells.len()
     }
+}
+
+pub fn count_energized(grid: &Grid, starting: Coordinate, direction: Direction) -> usize {
+    Contraption::new(grid, &Registry::default())
+        .unwrap()
+        .count_energized(starting, direction)
+}
 
-    energized.iter().map(|l| l.position).unique().count()
+// between splits a beam is a single fixed path. each split, a cell along with
+// the beams leaving it, becomes a node linked to the splits those beams go on
+// to reach, along with the cells they cross. a beam from anywhere then
+// energizes its own path plus everything reachable from the first split it
+// hits, which is worked out once per strongly connected component and shared
+// between every start
+pub struct BeamGraph {
+    contraption: Contraption,
+    splits: Graph<Split, ()>,
+    component: Vec<usize>,
+    reachable: Vec<Cells>,
+}
+
+type Split = (Coordinate, Vec<Direction>);
+
+impl BeamGraph {
+    pub fn new(grid: &Grid) -> BeamGraph {
+        BeamGraph::from_contraption(Contraption::new(grid, &Registry::default()).unwrap())
+    }
+
+    pub fn from_contraption(contraption: Contraption) -> BeamGraph {
+        let mut beams = BeamGraph {
+            contraption,
+            splits: Graph::directed(),
+            component: Vec::new(),
+            reachable: Vec::new(),
+        };
+
+        // every way a beam can split is a node, even those no beam from the
+        // edge reaches, so a beam can be started from anywhere
+        let mut queue: Vec<Split> = beams
+            .contraption
+            .elements
+            .iter()
+            .flat_map(|(position, element)| {
+                [North, South, East, West]
+                    .into_iter()
+                    .map(|direction| (*position, element.outputs(direction)))
+            })
+            .filter(|(_, directions)| directions.len() > 1)
+            .collect();
+        let mut seen = HashSet::new();
+        let mut nodes = Vec::new();
+
+        while let Some(split) = queue.pop() {
+            if !seen.insert(split.clone()) {
+                continue;
+            }
+
+            let (position, directions) = &split;
+            let mut cells = beams.empty_cells();
+            cells.insert(beams.index(position));
+            let mut next_splits = Vec::new();
+
+            for direction in directions {
+                let (path, next) = beams.trace(*position + *direction, *direction);
+                for cell in path {
+                    cells.insert(beams.index(&cell));
+                }
+                if let Some(next) = next {
+                    next_splits.push(next.clone());
+                    queue.push(next);
+                }
+            }
+
+            nodes.push((split, cells, next_splits));
+        }
+
+        // interning in the order we visited keeps ids lined up with the cells
+        for (split, _, _) in &nodes {
+            beams.splits.intern(split.clone());
+        }
+        for (split, _, next_splits) in &nodes {
+            for next in next_splits {
+                beams.splits.add_edge(split.clone(), next.clone(), ());
+            }
+        }
+
+        let energized = nodes.into_iter().map(|(_, cells, _)| cells).collect();
+        beams.with_components(energized)
+    }
+
+    // components come out with everything they lead to ahead of them, so each
+    // one only needs to union in the components it links to directly
+    fn with_components(mut self, energized: Vec<Cells>) -> BeamGraph {
+        let components = self.splits.strongly_connected_components();
+        self.component = vec![0; self.splits.len()];
+        for (index, members) in components.iter().enumerate() {
+            for member in members {
+                self.component[*member] = index;
+            }
+        }
+
+        for (index, members) in components.iter().enumerate() {
+            let mut reachable = self.empty_cells();
+            for member in members {
+                reachable.union(&energized[*member]);
+                for (next, _) in self.splits.neighbors(*member) {
+                    if self.component[*next] != index {
+                        reachable.union(&self.reachable[self.component[*next]]);
+                    }
+                }
+            }
+            self.reachable.push(reachable);
+        }
+
+        self
+    }
+
+    pub fn energized(&self, start: Coordinate, direction: Direction) -> usize {
+        let (path, split) = self.trace(start, direction);
+        let mut cells = match split {
+            Some(split) => self.reachable[self.component[self.splits.id(&split).unwrap()]].clone(),
+            None => self.empty_cells(),
+        };
+        for cell in path {
+            cells.insert(self.index(&cell));
+        }
+        cells.count()
+    }
+
+    // follows a beam until it leaves the grid, is absorbed, comes back around
+    // on itself or splits, giving back the cells it crossed and the split if
+    // there was one
+    fn trace(
+        &self,
+        mut position: Coordinate,
+        mut direction: Direction,
+    ) -> (Vec<Coordinate>, Option<Split>) {
+        let mut path = Vec::new();
+        let mut seen = HashSet::new();
+
+        while self.contraption.in_bounds(&position) && seen.insert((position, direction)) {
+            path.push(position);
+            let (from, directions) = self.contraption.outputs(position, direction);
+            match directions[..] {
+                [] => break,
+                [next] => {
+                    path.push(from);
+                    position = from + next;
+                    direction = next;
+                }
+                _ => return (path, Some((from, directions))),
+            }
+        }
+        (path, None)
+    }
+
+    fn index(&self, coord: &Coordinate) -> usize {
+        let bounds = &self.contraption.bounds;
+        let local = Coordinate(coord.0 - bounds.x.min, coord.1 - bounds.y.min);
+        (local.1 * bounds.width() + local.0) as usize
+    }
+
+    fn empty_cells(&self) -> Cells {
+        let bounds = &self.contraption.bounds;
+        Cells::new((bounds.width() * bounds.height()) as usize)
+    }
+}
+
+// a bitset over the cells of the grid, cheap to copy and union
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cells(Vec<u64>);
+impl Cells {
+    fn new(size: usize) -> Cells {
+        Cells(vec![0; size.div_ceil(64)])
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    fn union(&mut self, other: &Cells) {
+        for (word, other) in self.0.iter_mut().zip(&other.0) {
+            *word |= other;
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
 }
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
@@ -109,7 +407,102 @@ mod tests {
     #[test]
     fn test_part_two() {
         assert_eq!(51, part_two(EXAMPLE));
-        // a little slow to include by default as part of comprehensive test suite ~1.5s
-        //assert_eq!(7759, part_two(INPUT));
+        assert_eq!(7759, part_two(INPUT));
+    }
+
+    #[test]
+    fn beam_graph_matches_simulation() {
+        for input in [EXAMPLE, INPUT] {
+            let grid = Grid::parse(input);
+            let beams = BeamGraph::new(&grid);
+            for (start, direction) in entry_points(&grid.bounds).step_by(23) {
+                assert_eq!(
+                    count_energized(&grid, start, direction),
+                    beams.energized(start, direction),
+                    "entering at {} heading {}",
+                    start,
+                    direction
+                );
+            }
+        }
+
+        // starts don't have to be on the edge
+        let grid = Grid::parse(EXAMPLE);
+        let beams = BeamGraph::new(&grid);
+        assert_eq!(
+            count_energized(&grid, Coordinate(4, 6), North),
+            beams.energized(Coordinate(4, 6), North)
+        );
+    }
+
+    #[test]
+    fn beam_graph_starts_anywhere() {
+        // a beam out of the splitter circles the mirrors and comes back
+        // through the splitter end on, round and round forever
+        let looping = "/.\\\n...\n|..\n...\n\\./";
+        // the splitters in the middle can't be reached from any edge
+        let enclosed = "....-\\\n...|||\n\\...|\\\n|\\/\\./\n|...\\/\n...-\\\\";
+
+        for input in [looping, enclosed, EXAMPLE] {
+            let grid = Grid::parse(input);
+            let beams = BeamGraph::new(&grid);
+            for start in grid.points.keys() {
+                for direction in [North, South, East, West] {
+                    assert_eq!(
+                        count_energized(&grid, *start, direction),
+                        beams.energized(*start, direction),
+                        "starting at {} heading {}",
+                        start,
+                        direction
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn custom_elements() {
+        let mut registry = Registry::default();
+        registry.register('#', Element::Absorber);
+        registry.register('+', Element::Splitter(vec![North, South, East, West]));
+        registry.register('@', Element::Portal);
+        // only reflects beams heading east, up towards the north
+        registry.register('>', Element::Mirror(vec![(East, North)]));
+
+        let layout = Grid::parse(concat!(
+            "..#..\n", //
+            ".@...\n", "..+.>\n", ".....\n", "...@.",
+        ));
+        let contraption = Contraption::new(&layout, &registry).unwrap();
+
+        // in along the middle row, the splitter sends beams everywhere, the
+        // one heading north is absorbed and the one heading east turns north
+        assert_eq!(11, contraption.count_energized(Coordinate(0, 2), East));
+        // down the second column into the portal, out at the bottom and away
+        assert_eq!(3, contraption.count_energized(Coordinate(1, 0), South));
+        // heading west the one way mirror lets the beam straight through
+        assert_eq!(9, contraption.count_energized(Coordinate(4, 2), West));
+
+        let beams = BeamGraph::from_contraption(Contraption::new(&layout, &registry).unwrap());
+        for (start, direction) in layout
+            .points
+            .keys()
+            .flat_map(|start| [North, South, East, West].map(|direction| (*start, direction)))
+        {
+            assert_eq!(
+                contraption.count_energized(start, direction),
+                beams.energized(start, direction),
+                "entering at {} heading {}",
+                start,
+                direction
+            );
+        }
+
+        assert_eq!(
+            Err("unknown character '#' at (x:2, y:0)".to_string()),
+            Contraption::new(&layout, &Registry::default()).map(|_| ())
+        );
+        registry.register('*', Element::Portal);
+        assert!(Contraption::new(&Grid::parse("*.."), &registry).is_err());
     }
 }
@@ -4,26 +4,71 @@ use flat::grid::Grid;
 use pathfinding::prelude::dijkstra;
 
 pub fn part_one(input: &str) -> u32 {
-    solve(input, 0, 3)
+    solve(input, 0, 3).heat_loss
 }
 
 pub fn part_two(input: &str) -> u32 {
-    solve(input, 4, 10)
+    solve(input, 4, 10).heat_loss
 }
 
-fn solve(input: &str, min_momentum: u32, max_momentum: u32) -> u32 {
+pub fn solve(input: &str, min_momentum: u32, max_momentum: u32) -> Route {
     let grid = Grid::parse(input);
     let starting = Node::new(grid.bounds.min(), East, 0);
     let destination = grid.bounds.max();
 
-    let (_, cost) = dijkstra(
+    let (path, heat_loss) = dijkstra(
         &starting,
         |c| c.neighbors(&grid, min_momentum, max_momentum),
-        |c| (c.position == destination && c.momentum >= min_momentum),
+        |c| c.position == destination && c.momentum >= min_momentum,
     )
     .unwrap();
 
-    cost
+    // the starting block doesn't count, we've not moved into it
+    let steps = path
+        .iter()
+        .skip(1)
+        .map(|node| Step {
+            position: node.position,
+            direction: node.direction,
+            momentum: node.momentum,
+            heat_loss: heat_loss_at(&grid, &node.position),
+        })
+        .collect();
+
+    Route { heat_loss, steps }
+}
+
+fn heat_loss_at(grid: &Grid, position: &Coordinate) -> u32 {
+    grid.get(position).unwrap().to_digit(10).unwrap()
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Step {
+    pub position: Coordinate,
+    pub direction: Direction,
+    // how many blocks in a row we've moved in this direction, this one included
+    pub momentum: u32,
+    pub heat_loss: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Route {
+    pub heat_loss: u32,
+    pub steps: Vec<Step>,
+}
+impl Route {
+    // the city with every block we moved into replaced by an arrow showing
+    // the direction we entered it, as the puzzle draws it
+    pub fn render(&self, grid: &Grid) -> String {
+        let mut overlay = Grid::new();
+        for (coord, c) in &grid.points {
+            overlay.insert(*coord, *c);
+        }
+        for step in &self.steps {
+            overlay.insert(step.position, step.direction.char());
+        }
+        overlay.format_default()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
@@ -48,11 +93,11 @@ impl Node {
             return vec![
                 (
                     Node::new(Coordinate(0, 1), South, 1),
-                    grid.get(&Coordinate(0, 1)).unwrap().to_digit(10).unwrap(),
+                    heat_loss_at(grid, &Coordinate(0, 1)),
                 ),
                 (
                     Node::new(Coordinate(1, 0), East, 1),
-                    grid.get(&Coordinate(1, 0)).unwrap().to_digit(10).unwrap(),
+                    heat_loss_at(grid, &Coordinate(1, 0)),
                 ),
             ];
         }
@@ -78,7 +123,7 @@ impl Node {
         .filter(|n| grid.is_some(&n.position))
         .filter(|n| n.momentum <= max_momentum)
         .filter(|n| n.direction == self.direction || self.momentum >= min_momentum)
-        .map(|n| (*n, grid.get(&n.position).unwrap().to_digit(10).unwrap()))
+        .map(|n| (*n, heat_loss_at(grid, &n.position)))
         .collect()
     }
 }
@@ -101,4 +146,55 @@ mod tests {
         assert_eq!(94, part_two(EXAMPLE));
         assert_eq!(801, part_two(INPUT));
     }
+
+    #[test]
+    fn renders_the_route() {
+        let grid = Grid::parse(EXAMPLE);
+        let route = solve(EXAMPLE, 0, 3);
+        assert_eq!(
+            concat!(
+                "2>>34^>>>1323\n",
+                "32v>>>35v5623\n",
+                "32552456v>>54\n",
+                "3446585845v52\n",
+                "4546657867v>6\n",
+                "14385987984v4\n",
+                "44578769877v6\n",
+                "36378779796v>\n",
+                "465496798688v\n",
+                "456467998645v\n",
+                "12246868655<v\n",
+                "25465488877v5\n",
+                "43226746555v>",
+            ),
+            route.render(&grid)
+        );
+        assert_eq!(
+            route.heat_loss,
+            route.steps.iter().map(|step| step.heat_loss).sum::<u32>()
+        );
+        assert!(route.steps.iter().all(|step| step.momentum <= 3));
+        assert_eq!(
+            Some(&grid.bounds.max()),
+            route.steps.last().map(|step| &step.position)
+        );
+
+        // ultra crucibles have to move at least 4 blocks before turning or stopping
+        let route = solve(EXAMPLE, 4, 10);
+        assert_eq!(
+            "2>>>>>>>>1323\n32154535v5623",
+            route
+                .render(&grid)
+                .lines()
+                .take(2)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        for turn in route.steps.windows(2) {
+            if turn[0].direction != turn[1].direction {
+                assert!(turn[0].momentum >= 4);
+            }
+        }
+        assert!(route.steps.last().unwrap().momentum >= 4);
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ops::Range;
 use std::option::Option;
 use strings::split_last;
@@ -7,66 +7,444 @@ const STARTING_WORKFLOW: &str = "in";
 
 pub fn part_one(input: &str) -> i32 {
     let (workflows, parts) = parse(input);
+    let program = compile(&workflows);
     parts
         .iter()
-        .filter(|part| is_accepted(part, workflows.get(STARTING_WORKFLOW).unwrap(), &workflows))
+        .filter(|part| program.evaluate(part))
         .map(|part| part.rating())
         .sum()
 }
 
-fn is_accepted(part: &Part, workflow: &Workflow, workflows: &HashMap<String, Workflow>) -> bool {
-    match workflow.apply(part) {
-        Outcome::Accept => true,
-        Outcome::Reject => false,
-        Outcome::Workflow(id) => is_accepted(part, workflows.get(&id).unwrap(), workflows),
+pub fn part_two(input: &str) -> i64 {
+    let (workflows, _) = parse(input);
+    compile(&workflows)
+        .accepted_ranges(&default_bounds(&workflows))
+        .iter()
+        .map(|ranges| ranges.distinct_combinations())
+        .sum()
+}
+
+fn compile(workflows: &HashMap<String, Workflow>) -> Program {
+    match Program::compile(workflows) {
+        Ok(program) => program,
+        Err(diagnostics) => panic!("unable to compile workflows: {:?}", diagnostics),
     }
 }
 
-pub fn part_two(input: &str) -> i64 {
+// everything wrong with a set of workflows. the first three stop them from
+// compiling at all, the rest compile fine but point at rules that are dead
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Diagnostic {
+    MissingStart,
+    UndefinedWorkflow { workflow: String, reference: String },
+    Cycle(Vec<String>),
+    UnreachableWorkflow(String),
+    UnreachableRule { workflow: String, rule: usize },
+    AlwaysFires { workflow: String, rule: usize },
+    NeverFires { workflow: String, rule: usize },
+}
+
+// every attribute the rules test is taken to be rated from 1 to 4000
+pub fn analyze(input: &str) -> Vec<Diagnostic> {
     let (workflows, _) = parse(input);
+    analyze_within(input, &Ranges::uniform(&attributes(&workflows), 1..4001))
+}
 
-    let ranges = success_ranges(
-        &Ranges::default(),
-        workflows.get(STARTING_WORKFLOW).unwrap(),
-        0,
-        &workflows,
-    );
+// every attribute some rule tests, sorted
+fn attributes(workflows: &HashMap<String, Workflow>) -> Vec<&str> {
+    let mut attributes: Vec<_> = workflows
+        .values()
+        .flat_map(|workflow| workflow.rules.iter())
+        .map(|rule| rule.attribute.as_str())
+        .collect();
+    attributes.sort();
+    attributes.dedup();
+    attributes
+}
 
-    ranges
-        .iter()
-        .map(|ranges| ranges.distinct_combinations())
-        .sum()
+// the puzzle's xmas ratings, plus any other attribute the rules test, all
+// rated from 1 to 4000
+fn default_bounds(workflows: &HashMap<String, Workflow>) -> Ranges {
+    let mut attributes = attributes(workflows);
+    for xmas in ["x", "m", "a", "s"] {
+        if !attributes.contains(&xmas) {
+            attributes.push(xmas);
+        }
+    }
+    Ranges::uniform(&attributes, 1..4001)
+}
+
+pub fn analyze_within(input: &str, bounds: &Ranges) -> Vec<Diagnostic> {
+    let (workflows, _) = parse(input);
+    match Program::compile(&workflows) {
+        Ok(program) => program.diagnostics(&workflows, bounds),
+        Err(diagnostics) => diagnostics,
+    }
+}
+
+// every part in the input along with the path it took through the workflows
+pub fn explain(input: &str) -> Vec<Explanation> {
+    let (workflows, parts) = parse(input);
+    let program = compile(&workflows);
+    parts.iter().map(|part| program.explain(part)).collect()
+}
+
+pub fn accepted_paths(input: &str) -> Vec<AcceptedRange> {
+    let (workflows, _) = parse(input);
+    compile(&workflows).accepted_paths(&default_bounds(&workflows))
+}
+
+// a workflow a part passed through, and the rule that sent it on from there,
+// none meaning no rule matched and it took the fallback
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Step {
+    pub workflow: String,
+    pub rule: Option<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Explanation {
+    pub accepted: bool,
+    pub trail: Vec<Step>,
+}
+
+// formatted the way the puzzle walks through its examples: in -> qqz -> A
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in &self.trail {
+            write!(f, "{} -> ", step.workflow)?;
+        }
+        f.write_str(match self.accepted {
+            true => "A",
+            false => "R",
+        })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct AcceptedRange {
+    pub ranges: Ranges,
+    pub path: Vec<Step>,
+}
+
+// where a rule or fallback sends a part, other workflows are linked by their
+// index in the program rather than copied in, so shared workflows are only
+// ever compiled once
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Accept,
+    Reject,
+    Workflow(usize),
+}
+
+#[derive(Debug)]
+struct Compiled {
+    id: String,
+    rules: Vec<(Rule, Target)>,
+    fallback: Target,
+}
+
+// what the walk over every range of ratings comes across, each test
+// remembers the workflow and rule it came from for reporting
+enum Visit<'a> {
+    Accept,
+    Reject,
+    Test {
+        workflow: &'a str,
+        rule: usize,
+        test: &'a Rule,
+    },
 }
 
-fn success_ranges(
-    ranges: &Ranges,
-    workflow: &Workflow,
-    rule_idx: usize,
-    workflows: &HashMap<String, Workflow>,
-) -> Vec<Ranges> {
-    match workflow.rules.get(rule_idx) {
-        Some(rule) => match rule.test_range(ranges) {
-            Some((true_range, false_range)) => {
-                let true_path = match &rule.outcome {
-                    Outcome::Accept => vec![true_range],
-                    Outcome::Reject => vec![],
-                    Outcome::Workflow(id) => {
-                        success_ranges(&true_range, workflows.get(id).unwrap(), 0, workflows)
+#[derive(Debug, Default, Clone, Copy)]
+struct Coverage {
+    passed: bool,
+    failed: bool,
+}
+
+pub struct Program {
+    workflows: Vec<Compiled>,
+    start: usize,
+}
+impl Program {
+    pub fn compile(workflows: &HashMap<String, Workflow>) -> Result<Program, Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        if !workflows.contains_key(STARTING_WORKFLOW) {
+            diagnostics.push(Diagnostic::MissingStart);
+        }
+
+        for id in sorted_ids(workflows) {
+            for reference in workflows[id].references() {
+                if !workflows.contains_key(reference) {
+                    diagnostics.push(Diagnostic::UndefinedWorkflow {
+                        workflow: id.to_string(),
+                        reference: reference.to_string(),
+                    });
+                }
+            }
+        }
+
+        diagnostics.extend(find_cycles(workflows).into_iter().map(Diagnostic::Cycle));
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        let ids = sorted_ids(workflows);
+        let indexes: HashMap<&str, usize> = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (*id, index))
+            .collect();
+        let target = |outcome: &Outcome| match outcome {
+            Outcome::Accept => Target::Accept,
+            Outcome::Reject => Target::Reject,
+            Outcome::Workflow(id) => Target::Workflow(indexes[id.as_str()]),
+        };
+
+        let compiled = ids
+            .iter()
+            .map(|id| {
+                let workflow = &workflows[*id];
+                Compiled {
+                    id: workflow.id.clone(),
+                    rules: workflow
+                        .rules
+                        .iter()
+                        .map(|rule| (rule.clone(), target(&rule.outcome)))
+                        .collect(),
+                    fallback: target(&workflow.fallback),
+                }
+            })
+            .collect();
+
+        Ok(Program {
+            workflows: compiled,
+            start: indexes[STARTING_WORKFLOW],
+        })
+    }
+
+    pub fn evaluate(&self, part: &Part) -> bool {
+        self.explain(part).accepted
+    }
+
+    pub fn explain(&self, part: &Part) -> Explanation {
+        let mut trail = Vec::new();
+        let mut target = Target::Workflow(self.start);
+        loop {
+            let workflow = match target {
+                Target::Accept => {
+                    return Explanation {
+                        accepted: true,
+                        trail,
                     }
-                };
-                let false_path = success_ranges(&false_range, workflow, rule_idx + 1, workflows);
-                true_path.into_iter().chain(false_path).collect()
+                }
+                Target::Reject => {
+                    return Explanation {
+                        accepted: false,
+                        trail,
+                    }
+                }
+                Target::Workflow(index) => &self.workflows[index],
+            };
+
+            let matched = workflow
+                .rules
+                .iter()
+                .position(|(rule, _)| rule.test_part(part));
+            trail.push(Step {
+                workflow: workflow.id.clone(),
+                rule: matched,
+            });
+            target = match matched {
+                Some(rule) => workflow.rules[rule].1,
+                None => workflow.fallback,
+            };
+        }
+    }
+
+    pub fn accepted_ranges(&self, bounds: &Ranges) -> Vec<Ranges> {
+        self.accepted_paths(bounds)
+            .into_iter()
+            .map(|accepted| accepted.ranges)
+            .collect()
+    }
+
+    // each hyper-rectangle of accepted ratings along with the path of
+    // workflows and rules that every part inside of it takes
+    pub fn accepted_paths(&self, bounds: &Ranges) -> Vec<AcceptedRange> {
+        let mut accepted = Vec::new();
+        self.walk(
+            Target::Workflow(self.start),
+            bounds.clone(),
+            &mut Vec::new(),
+            &mut |visit, ranges, path| {
+                if let Visit::Accept = visit {
+                    accepted.push(AcceptedRange {
+                        ranges: ranges.clone(),
+                        path: path.to_vec(),
+                    });
+                }
+            },
+        );
+        accepted
+    }
+
+    // visits everything that some part can actually reach, along with the
+    // ranges of parts that reach it and the path they took to get there
+    fn walk(
+        &self,
+        target: Target,
+        ranges: Ranges,
+        path: &mut Vec<Step>,
+        visit: &mut impl FnMut(Visit, &Ranges, &[Step]),
+    ) {
+        let workflow = match target {
+            Target::Accept => return visit(Visit::Accept, &ranges, path),
+            Target::Reject => return visit(Visit::Reject, &ranges, path),
+            Target::Workflow(index) => &self.workflows[index],
+        };
+
+        // each rule takes the parts it matches, the rest carry on to the next
+        let mut remaining = Some(ranges);
+        for (index, (rule, next)) in workflow.rules.iter().enumerate() {
+            let Some(ranges) = remaining else {
+                return;
+            };
+            visit(
+                Visit::Test {
+                    workflow: &workflow.id,
+                    rule: index,
+                    test: rule,
+                },
+                &ranges,
+                path,
+            );
+
+            let (pass_ranges, fail_ranges) = rule.split(&ranges);
+            if let Some(pass_ranges) = pass_ranges {
+                path.push(Step {
+                    workflow: workflow.id.clone(),
+                    rule: Some(index),
+                });
+                self.walk(*next, pass_ranges, path, visit);
+                path.pop();
             }
-            None => vec![],
-        },
-        None => match &workflow.fallback {
-            Outcome::Accept => vec![ranges.clone()],
-            Outcome::Reject => vec![],
-            Outcome::Workflow(id) => {
-                success_ranges(ranges, workflows.get(id).unwrap(), 0, workflows)
+            remaining = fail_ranges;
+        }
+
+        if let Some(ranges) = remaining {
+            path.push(Step {
+                workflow: workflow.id.clone(),
+                rule: None,
+            });
+            self.walk(workflow.fallback, ranges, path, visit);
+            path.pop();
+        }
+    }
+
+    fn diagnostics(
+        &self,
+        workflows: &HashMap<String, Workflow>,
+        bounds: &Ranges,
+    ) -> Vec<Diagnostic> {
+        let mut coverage: HashMap<(String, usize), Coverage> = HashMap::new();
+        let mut reached: BTreeSet<String> = BTreeSet::new();
+        self.walk(
+            Target::Workflow(self.start),
+            bounds.clone(),
+            &mut Vec::new(),
+            &mut |visit, ranges, _| {
+                if let Visit::Test {
+                    workflow,
+                    rule,
+                    test,
+                } = visit
+                {
+                    let (pass, fail) = test.split(ranges);
+                    let entry = coverage.entry((workflow.to_string(), rule)).or_default();
+                    entry.passed |= pass.is_some();
+                    entry.failed |= fail.is_some();
+                }
+            },
+        );
+
+        // a workflow referenced at all from "in" counts as reachable, even if
+        // no part can get there, its rules are reported as unreachable instead
+        let mut pending = vec![STARTING_WORKFLOW];
+        while let Some(id) = pending.pop() {
+            if reached.insert(id.to_string()) {
+                pending.extend(workflows[id].references());
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        for id in sorted_ids(workflows) {
+            if !reached.contains(id) {
+                diagnostics.push(Diagnostic::UnreachableWorkflow(id.to_string()));
+                continue;
+            }
+
+            for rule in 0..workflows[id].rules.len() {
+                let workflow = id.to_string();
+                match coverage.get(&(workflow.clone(), rule)) {
+                    None => diagnostics.push(Diagnostic::UnreachableRule { workflow, rule }),
+                    Some(Coverage { passed: false, .. }) => {
+                        diagnostics.push(Diagnostic::NeverFires { workflow, rule })
+                    }
+                    Some(Coverage { failed: false, .. }) => {
+                        diagnostics.push(Diagnostic::AlwaysFires { workflow, rule })
+                    }
+                    _ => (),
+                }
             }
-        },
+        }
+        diagnostics
+    }
+}
+
+fn sorted_ids(workflows: &HashMap<String, Workflow>) -> Vec<&str> {
+    let mut ids: Vec<_> = workflows.keys().map(|id| id.as_str()).collect();
+    ids.sort();
+    ids
+}
+
+// depth first search over workflow references, any reference back to a
+// workflow still on the stack closes a cycle. cycles are rotated to start
+// from their smallest id so the same cycle is only reported once
+fn find_cycles(workflows: &HashMap<String, Workflow>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        id: &'a str,
+        workflows: &'a HashMap<String, Workflow>,
+        stack: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+        cycles: &mut BTreeSet<Vec<String>>,
+    ) {
+        if let Some(start) = stack.iter().position(|s| *s == id) {
+            let mut cycle: Vec<_> = stack[start..].iter().map(|s| s.to_string()).collect();
+            let smallest = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap();
+            cycle.rotate_left(smallest);
+            cycles.insert(cycle);
+            return;
+        }
+        if done.contains(id) || !workflows.contains_key(id) {
+            return;
+        }
+
+        stack.push(id);
+        for reference in workflows[id].references() {
+            visit(reference, workflows, stack, done, cycles);
+        }
+        stack.pop();
+        done.insert(id);
     }
+
+    let mut cycles = BTreeSet::new();
+    let mut done = BTreeSet::new();
+    for id in sorted_ids(workflows) {
+        visit(id, workflows, &mut Vec::new(), &mut done, &mut cycles);
+    }
+    cycles.into_iter().collect()
 }
 
 fn parse(input: &str) -> (HashMap<String, Workflow>, Vec<Part>) {
@@ -82,7 +460,7 @@ fn parse(input: &str) -> (HashMap<String, Workflow>, Vec<Part>) {
 }
 
 #[derive(Debug)]
-struct Workflow {
+pub struct Workflow {
     id: String,
     rules: Vec<Rule>,
     fallback: Outcome,
@@ -90,27 +468,37 @@ struct Workflow {
 impl Workflow {
     fn parse(input: &str) -> Self {
         let (id, rest) = input.split_once('{').unwrap();
-        let (rules_str, outcome_str) = split_last(rest.strip_suffix('}').unwrap(), ',');
-        let rules: Vec<_> = rules_str.split(',').map(Rule::parse).collect();
+        let rest = rest.strip_suffix('}').unwrap();
+        let (rules, fallback) = match rest.contains(',') {
+            true => {
+                let (rules_str, outcome_str) = split_last(rest, ',');
+                (rules_str.split(',').map(Rule::parse).collect(), outcome_str)
+            }
+            false => (Vec::new(), rest),
+        };
 
         Workflow {
             id: id.to_string(),
             rules,
-            fallback: Outcome::parse(outcome_str),
+            fallback: Outcome::parse(fallback),
         }
     }
 
-    fn apply(&self, part: &Part) -> Outcome {
-        match self.rules.iter().find(|rule| rule.test_part(part)) {
-            Some(rule) => rule.outcome.clone(),
-            None => self.fallback.clone(),
-        }
+    fn references(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .map(|rule| &rule.outcome)
+            .chain(std::iter::once(&self.fallback))
+            .filter_map(|outcome| match outcome {
+                Outcome::Workflow(id) => Some(id.as_str()),
+                _ => None,
+            })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Rule {
-    variable: Variable,
+    attribute: String,
     condition: Condition,
     value: i32,
     outcome: Outcome,
@@ -118,9 +506,9 @@ struct Rule {
 impl Rule {
     fn parse(input: &str) -> Self {
         let (rule_str, outcome) = input.split_once(':').unwrap();
-        let (variable, value) = rule_str.split_once(|c| c == '>' || c == '<').unwrap();
+        let (attribute, value) = rule_str.split_once(['>', '<']).unwrap();
         Rule {
-            variable: Variable::parse(variable),
+            attribute: attribute.to_string(),
             condition: Condition::parse(input),
             value: value.parse().unwrap(),
             outcome: Outcome::parse(outcome),
@@ -128,7 +516,7 @@ impl Rule {
     }
 
     fn test_part(&self, part: &Part) -> bool {
-        let part_value = self.variable.get(part);
+        let part_value = part.get(&self.attribute);
         match &self.condition {
             Condition::GreaterThan => part_value > self.value,
             Condition::LessThan => part_value < self.value,
@@ -137,66 +525,33 @@ impl Rule {
 
     // given some range values, and this rule, we will split the range into two
     // branches, once success branch for this rule and one failure branch based
-    // on the state of the rule
-    fn test_range(&self, ranges: &Ranges) -> Option<(Ranges, Ranges)> {
-        let existing = match self.variable {
-            Variable::X => &ranges.x,
-            Variable::M => &ranges.m,
-            Variable::A => &ranges.a,
-            Variable::S => &ranges.s,
+    // on the state of the rule, either of which is none if no value ends up there
+    fn split(&self, ranges: &Ranges) -> (Option<Ranges>, Option<Ranges>) {
+        let existing = match ranges.get(&self.attribute) {
+            Some(existing) => existing,
+            None => panic!("no bounds given for attribute {}", self.attribute),
+        };
+        let branch = |range: Range<i32>| match range.is_empty() {
+            true => None,
+            false => Some(ranges.clone_with(&self.attribute, range)),
         };
 
-        if !existing.contains(&self.value) {
-            return None;
-        }
-
-        Some((
-            ranges.clone_with(&self.variable, self.split_ranges(existing, true)),
-            ranges.clone_with(&self.variable, self.split_ranges(existing, false)),
-        ))
-    }
-
-    fn split_ranges(&self, current: &Range<i32>, success: bool) -> Range<i32> {
-        match (success, &self.condition) {
+        match self.condition {
             // example: a>1716: [false: 1..1717, true: 1717..4001]
-            (false, Condition::GreaterThan) => current.start..self.value + 1,
-            (true, Condition::GreaterThan) => self.value + 1..current.end,
+            Condition::GreaterThan => (
+                branch(existing.start.max(self.value + 1)..existing.end),
+                branch(existing.start..existing.end.min(self.value + 1)),
+            ),
             // example: a<2006: [true: 1..2006, false: 2006..4001]
-            (true, Condition::LessThan) => current.start..self.value,
-            (false, Condition::LessThan) => self.value..current.end,
+            Condition::LessThan => (
+                branch(existing.start..existing.end.min(self.value)),
+                branch(existing.start.max(self.value)..existing.end),
+            ),
         }
     }
 }
 
-#[derive(Debug)]
-enum Variable {
-    X,
-    M,
-    A,
-    S,
-}
-impl Variable {
-    fn parse(input: &str) -> Self {
-        match input {
-            "x" => Variable::X,
-            "m" => Variable::M,
-            "a" => Variable::A,
-            "s" => Variable::S,
-            v => panic!("invalid variable: {}", v),
-        }
-    }
-
-    fn get(&self, part: &Part) -> i32 {
-        match self {
-            Variable::X => part.x,
-            Variable::M => part.m,
-            Variable::A => part.a,
-            Variable::S => part.s,
-        }
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Condition {
     GreaterThan,
     LessThan,
@@ -227,66 +582,80 @@ impl Outcome {
 }
 
 #[derive(Debug)]
-struct Part {
-    x: i32,
-    m: i32,
-    a: i32,
-    s: i32,
+pub struct Part {
+    ratings: BTreeMap<String, i32>,
 }
 impl Part {
     fn parse(input: &str) -> Self {
-        let values: Vec<i32> = input
-            .split(|c| c == '=' || c == ',' || c == '}')
-            .flat_map(|s| s.parse())
+        let ratings = input
+            .trim_matches(['{', '}'])
+            .split(',')
+            .map(|rating| {
+                let (attribute, value) = rating.split_once('=').unwrap();
+                (attribute.to_string(), value.parse().unwrap())
+            })
             .collect();
-        assert!(values.len() == 4);
 
-        Part {
-            x: values[0],
-            m: values[1],
-            a: values[2],
-            s: values[3],
+        Part { ratings }
+    }
+
+    fn get(&self, attribute: &str) -> i32 {
+        match self.ratings.get(attribute) {
+            Some(value) => *value,
+            None => panic!("part has no rating for attribute {}", attribute),
         }
     }
 
     fn rating(&self) -> i32 {
-        self.x + self.m + self.a + self.s
+        self.ratings.values().sum()
     }
 }
 
-// non-overlapping set of ranges that apply to each variable rule
-#[derive(Debug, Clone)]
-struct Ranges {
-    x: Range<i32>,
-    m: Range<i32>,
-    a: Range<i32>,
-    s: Range<i32>,
+// non-overlapping set of ranges that apply to each attribute, with any number
+// of attributes each with their own bounds. defaults to the puzzle's xmas
+// attributes all rated from 1 to 4000
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Ranges {
+    ranges: BTreeMap<String, Range<i32>>,
 }
 impl Ranges {
-    fn default() -> Self {
+    pub fn new<'a>(bounds: impl IntoIterator<Item = (&'a str, Range<i32>)>) -> Self {
         Ranges {
-            x: 1..4001,
-            m: 1..4001,
-            a: 1..4001,
-            s: 1..4001,
+            ranges: bounds
+                .into_iter()
+                .map(|(attribute, range)| (attribute.to_string(), range))
+                .collect(),
         }
     }
 
-    fn new(x: Range<i32>, m: Range<i32>, a: Range<i32>, s: Range<i32>) -> Self {
-        Ranges { x, m, a, s }
+    pub fn uniform(attributes: &[&str], bounds: Range<i32>) -> Self {
+        Ranges::new(
+            attributes
+                .iter()
+                .map(|attribute| (*attribute, bounds.clone())),
+        )
     }
 
-    fn clone_with(&self, variable: &Variable, ranges: Range<i32>) -> Self {
-        match variable {
-            Variable::X => Ranges::new(ranges, self.m.clone(), self.a.clone(), self.s.clone()),
-            Variable::M => Ranges::new(self.x.clone(), ranges, self.a.clone(), self.s.clone()),
-            Variable::A => Ranges::new(self.x.clone(), self.m.clone(), ranges, self.s.clone()),
-            Variable::S => Ranges::new(self.x.clone(), self.m.clone(), self.a.clone(), ranges),
-        }
+    pub fn get(&self, attribute: &str) -> Option<&Range<i32>> {
+        self.ranges.get(attribute)
     }
 
-    fn distinct_combinations(&self) -> i64 {
-        self.x.len() as i64 * self.m.len() as i64 * self.a.len() as i64 * self.s.len() as i64
+    fn clone_with(&self, attribute: &str, range: Range<i32>) -> Self {
+        let mut ranges = self.clone();
+        ranges.ranges.insert(attribute.to_string(), range);
+        ranges
+    }
+
+    pub fn distinct_combinations(&self) -> i64 {
+        self.ranges
+            .values()
+            .map(|range| range.len() as i64)
+            .product()
+    }
+}
+impl Default for Ranges {
+    fn default() -> Self {
+        Ranges::uniform(&["x", "m", "a", "s"], 1..4001)
     }
 }
 
@@ -308,4 +677,199 @@ mod tests {
         assert_eq!(167409079868000, part_two(EXAMPLE));
         assert_eq!(132186256794011, part_two(INPUT));
     }
+
+    #[test]
+    fn analyze_reports_broken_workflows() {
+        let broken = "in{x<10:ab,cd}\nab{m>5:in,R}\ncd{a<3:zz,A}\n\n{x=1,m=2,a=3,s=4}";
+        assert_eq!(
+            vec![
+                Diagnostic::UndefinedWorkflow {
+                    workflow: "cd".to_string(),
+                    reference: "zz".to_string()
+                },
+                Diagnostic::Cycle(vec!["ab".to_string(), "in".to_string()]),
+            ],
+            analyze(broken)
+        );
+
+        assert_eq!(
+            vec![Diagnostic::MissingStart],
+            analyze("ab{A}\n\n{x=1,m=2,a=3,s=4}")
+        );
+    }
+
+    #[test]
+    fn analyze_reports_dead_rules() {
+        let dead = "in{x<10:ab,x<5:R,A}\nab{x<20:A,m>4000:R,R}\nzz{A}\n\n{x=1,m=2,a=3,s=4}";
+        assert_eq!(
+            vec![
+                Diagnostic::AlwaysFires {
+                    workflow: "ab".to_string(),
+                    rule: 0
+                },
+                Diagnostic::UnreachableRule {
+                    workflow: "ab".to_string(),
+                    rule: 1
+                },
+                Diagnostic::NeverFires {
+                    workflow: "in".to_string(),
+                    rule: 1
+                },
+                Diagnostic::UnreachableWorkflow("zz".to_string()),
+            ],
+            analyze(dead)
+        );
+
+        assert_eq!(Vec::<Diagnostic>::new(), analyze(EXAMPLE));
+    }
+
+    #[test]
+    fn counts_attributes_outside_xmas() {
+        // q is rated 1 to 4000 like everything else, each of x, m, a and s
+        // multiply the count whether or not a rule tests them
+        let workflows = "in{q<2001:A,x>3000:A,R}\n\n{x=1,m=2,a=3,s=4}";
+        let xmas = 4000_i64 * 4000 * 4000;
+        assert_eq!(2000 * 4000 * xmas + 2000 * 1000 * xmas, part_two(workflows));
+
+        let accepted = accepted_paths(workflows);
+        assert_eq!(2, accepted.len());
+        assert_eq!(Some(&(1..2001)), accepted[0].ranges.get("q"));
+        assert_eq!(Some(&(3001..4001)), accepted[1].ranges.get("x"));
+    }
+
+    #[test]
+    fn counts_arbitrary_attributes() {
+        let workflows = "in{q<5:A,z>7:ok,R}\nok{q>8:R,A}\n\n{q=4,z=1}\n{q=6,z=9}\n{q=9,z=9}";
+        let (parsed, parts) = parse(workflows);
+        let program = compile(&parsed);
+        assert_eq!(
+            vec![true, true, false],
+            parts
+                .iter()
+                .map(|p| program.evaluate(p))
+                .collect::<Vec<_>>()
+        );
+
+        // q in 1..=4 with any z, or q in 5..=8 with z in 8..=10
+        let bounds = Ranges::uniform(&["q", "z"], 1..11);
+        let accepted = program.accepted_ranges(&bounds);
+        assert_eq!(
+            4 * 10 + 4 * 3,
+            accepted
+                .iter()
+                .map(|r| r.distinct_combinations())
+                .sum::<i64>()
+        );
+
+        let narrow = Ranges::new([("q", 3..7), ("z", 8..9)]);
+        assert_eq!(
+            4,
+            program
+                .accepted_ranges(&narrow)
+                .iter()
+                .map(|r| r.distinct_combinations())
+                .sum::<i64>()
+        );
+        assert_eq!(
+            vec![
+                Diagnostic::AlwaysFires {
+                    workflow: "in".to_string(),
+                    rule: 1
+                },
+                Diagnostic::NeverFires {
+                    workflow: "ok".to_string(),
+                    rule: 0
+                }
+            ],
+            analyze_within(workflows, &narrow)
+        );
+    }
+
+    #[test]
+    fn explains_each_part() {
+        let explanations: Vec<_> = explain(EXAMPLE).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "in -> qqz -> qs -> lnx -> A",
+                "in -> px -> rfg -> gd -> R",
+                "in -> qqz -> hdj -> pv -> A",
+                "in -> px -> qkq -> crn -> R",
+                "in -> px -> rfg -> A",
+            ],
+            explanations
+        );
+
+        let rejected = &explain(EXAMPLE)[1];
+        assert!(!rejected.accepted);
+        assert_eq!(
+            vec![
+                ("in", Some(0)),
+                ("px", None),
+                ("rfg", Some(0)),
+                ("gd", None)
+            ],
+            rejected
+                .trail
+                .iter()
+                .map(|step| (step.workflow.as_str(), step.rule))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn explains_accepted_ranges() {
+        let accepted = accepted_paths(EXAMPLE);
+        assert_eq!(
+            167409079868000,
+            accepted
+                .iter()
+                .map(|a| a.ranges.distinct_combinations())
+                .sum::<i64>()
+        );
+
+        // in s<1351 -> px a<2006 -> qkq x<1416 -> A
+        let first = &accepted[0];
+        assert_eq!(Some(&(1..1351)), first.ranges.get("s"));
+        assert_eq!(Some(&(1..2006)), first.ranges.get("a"));
+        assert_eq!(Some(&(1..1416)), first.ranges.get("x"));
+        assert_eq!(
+            vec!["in", "px", "qkq"],
+            first
+                .path
+                .iter()
+                .map(|step| step.workflow.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compiles_shared_workflows_once() {
+        // every workflow sends both its rule and its fallback on to the next,
+        // inlining them would double the work at every link in the chain
+        let length = 60;
+        let mut input = String::from("in{x<100:w1,w1}\n");
+        for link in 1..length {
+            input += &format!(
+                "w{}{{x<{}:w{},w{}}}\n",
+                link,
+                100 + link,
+                link + 1,
+                link + 1
+            );
+        }
+        input += &format!(
+            "w{}{{x<4000:A,R}}\n\n{{x=5,m=1,a=1,s=1}}\n{{x=4000,m=1,a=1,s=1}}",
+            length
+        );
+
+        assert_eq!(8, part_one(&input));
+        assert_eq!(3999 * 4000 * 4000 * 4000, part_two(&input));
+
+        let explanations = explain(&input);
+        assert_eq!(length + 1, explanations[0].trail.len());
+        assert_eq!(Some(0), explanations[0].trail[0].rule);
+        assert_eq!(None, explanations[1].trail[0].rule);
+        assert!(!explanations[1].accepted);
+        assert_eq!(Vec::<Diagnostic>::new(), analyze(&input));
+    }
 }
@@ -1,170 +1,460 @@
+use graph::graph::{Graph, NodeId};
 use math::lcm;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
 
-pub fn part_one(input: &str) -> i32 {
-    let modules = parse(input);
-    let mut processing: VecDeque<(String, String, bool)> = VecDeque::new();
-    let mut low_count = 0;
-    let mut high_count = 0;
-
+pub fn part_one(input: &str) -> usize {
+    let mut simulator = Simulator::parse(input).unwrap();
     for _ in 0..1000 {
-        processing.push_back(("button".to_string(), "roadcaster".to_string(), false));
-        while let Some((from, to, signal)) = processing.pop_front() {
-            match signal {
-                true => high_count += 1,
-                false => low_count += 1,
-            }
+        simulator.press();
+    }
+    let total = simulator.total();
+    total.low * total.high
+}
 
-            if let Some(refcell) = modules.get(&to) {
-                let mut module = refcell.borrow_mut();
+pub fn part_two(input: &str) -> usize {
+    analyze(input, "rx")
+        .unwrap()
+        .branches
+        .iter()
+        .map(|branch| branch.cycle)
+        .fold(1, lcm)
+}
 
-                if let Some(result) = module.pulse(&from, signal) {
-                    for destination in module.destinations.iter() {
-                        processing.push_back((to.clone(), destination.clone(), result));
-                    }
-                }
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Analysis {
+    pub conjunction: String,
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Branch {
+    pub feeder: String,
+    pub cycle: usize,
+}
+
+// the output is driven by a single conjunction, which only sends a low pulse
+// once all its inputs have sent high in the same press. each input is the end
+// of its own sub-circuit which sends high on a fixed cycle, so the answer is
+// the lcm of those cycles, as long as the sub-circuits really are independent
+pub fn analyze(input: &str, output: &str) -> Result<Analysis, String> {
+    let mut simulator = Simulator::parse(input)?;
+    let output = simulator
+        .id(output)
+        .ok_or_else(|| format!("no module sends to {}", output))?;
+
+    let conjunction = match simulator.wiring.incoming(output) {
+        [conjunction] if simulator.prefix(*conjunction) == Some('&') => *conjunction,
+        _ => return Err("output isn't fed by a single conjunction".to_string()),
+    };
+    let feeders = simulator.wiring.incoming(conjunction).to_vec();
+
+    let mut claimed: HashMap<NodeId, NodeId> = HashMap::new();
+    for feeder in feeders.iter() {
+        for id in simulator.upstream(*feeder) {
+            if id == conjunction {
+                return Err(format!("{} feeds back into itself", simulator.name(id)));
+            }
+            if let Some(other) = claimed.insert(id, *feeder) {
+                return Err(format!(
+                    "{} and {} share module {}",
+                    simulator.name(other),
+                    simulator.name(*feeder),
+                    simulator.name(id)
+                ));
             }
         }
     }
-    low_count * high_count
-}
 
-pub fn part_two(input: &str, rx_sender: &str) -> usize {
-    parse(input)
+    let hits = Rc::new(RefCell::new(vec![Vec::new(); simulator.wiring.len()]));
+    let recorder = hits.clone();
+    simulator.on_pulse(move |pulse| {
+        if pulse.to == conjunction && pulse.signal {
+            recorder.borrow_mut()[pulse.from].push(pulse.press);
+        }
+    });
+
+    // pressing until each feeder has fired twice lets us check it really is
+    // on a cycle starting from the first press, rather than only an offset
+    while feeders.iter().any(|f| hits.borrow()[*f].len() < 2) {
+        if simulator.presses() >= MAX_PRESSES {
+            return Err(format!("no cycle found in {} presses", MAX_PRESSES));
+        }
+        simulator.press();
+    }
+
+    let hits = hits.borrow();
+    let branches = feeders
         .iter()
-        .filter(|(_, module)| {
-            module
-                .borrow()
-                .destinations
-                .contains(&rx_sender.to_string())
+        .map(|feeder| match hits[*feeder][..2] {
+            [first, second] if second == first * 2 => Ok(Branch {
+                feeder: simulator.name(*feeder).to_string(),
+                cycle: first,
+            }),
+            _ => Err(format!(
+                "{} doesn't fire on a clean cycle",
+                simulator.name(*feeder)
+            )),
         })
-        .map(|(id, _)| id.to_string())
-        .map(|id| detect_signal(&parse(input), &id, rx_sender))
-        .fold(1, lcm)
+        .collect::<Result<_, _>>()?;
+
+    Ok(Analysis {
+        conjunction: simulator.name(conjunction).to_string(),
+        branches,
+    })
 }
 
-fn detect_signal(
-    modules: &HashMap<String, RefCell<Module>>,
-    detect_from: &str,
-    detect_to: &str,
-) -> usize {
-    let mut processing: VecDeque<(String, String, bool)> = VecDeque::new();
-    let mut count = 0;
-
-    loop {
-        count += 1;
-        processing.push_back(("button".to_string(), "roadcaster".to_string(), false));
-        while let Some((from, to, signal)) = processing.pop_front() {
-            if &to == detect_to && &from == detect_from && signal {
-                return count;
-            }
+const MAX_PRESSES: usize = 100_000;
 
-            if let Some(refcell) = modules.get(&to) {
-                let mut module = refcell.borrow_mut();
+// anything that can sit in the circuit. connect is called once for every
+// module wired into this one before the first pulse is sent, pulse returns
+// the signal to send on to every destination, or none to stay quiet
+pub trait Module {
+    fn connect(&mut self, _from: NodeId) {}
+    fn pulse(&mut self, from: NodeId, signal: bool) -> Option<bool>;
 
-                if let Some(result) = module.pulse(&from, signal) {
-                    for destination in module.destinations.iter() {
-                        processing.push_back((to.clone(), destination.clone(), result));
-                    }
-                }
+    // whatever the module remembers between pulses, used when tracing
+    fn state(&self) -> Vec<bool> {
+        Vec::new()
+    }
+}
+
+pub struct Broadcaster;
+impl Module for Broadcaster {
+    fn pulse(&mut self, _from: NodeId, signal: bool) -> Option<bool> {
+        Some(signal)
+    }
+}
+
+#[derive(Default)]
+pub struct FlipFlop {
+    on: bool,
+}
+impl Module for FlipFlop {
+    fn pulse(&mut self, _from: NodeId, signal: bool) -> Option<bool> {
+        match signal {
+            true => None,
+            false => {
+                self.on = !self.on;
+                Some(self.on)
             }
         }
     }
+
+    fn state(&self) -> Vec<bool> {
+        vec![self.on]
+    }
 }
 
-fn parse(input: &str) -> HashMap<String, RefCell<Module>> {
-    let mut modules: HashMap<_, _> = input
-        .lines()
-        .map(Module::parse)
-        .map(|m| (m.id.to_string(), m))
-        .collect();
+#[derive(Default)]
+pub struct Conjunction {
+    inputs: BTreeMap<NodeId, bool>,
+}
+impl Module for Conjunction {
+    fn connect(&mut self, from: NodeId) {
+        self.inputs.insert(from, false);
+    }
+
+    fn pulse(&mut self, from: NodeId, signal: bool) -> Option<bool> {
+        self.inputs.insert(from, signal);
+        Some(!self.inputs.values().all(|v| *v))
+    }
+
+    // the last pulse remembered from each input, ordered by input id
+    fn state(&self) -> Vec<bool> {
+        self.inputs.values().copied().collect()
+    }
+}
+
+// maps the prefix in front of a module name to a way to build it, the
+// broadcaster is the only module recognised by its name rather than a prefix
+pub struct Registry {
+    kinds: HashMap<char, fn() -> Box<dyn Module>>,
+}
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            kinds: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, prefix: char, build: fn() -> Box<dyn Module>) {
+        self.kinds.insert(prefix, build);
+    }
+}
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register('%', || Box::<FlipFlop>::default());
+        registry.register('&', || Box::<Conjunction>::default());
+        registry
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Pulse {
+    pub press: usize,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub signal: bool,
+}
 
-    let destinations: HashMap<_, _> =
-        modules
+// a delivered pulse along with the state the receiving module was left in
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Event {
+    pub pulse: Pulse,
+    pub state: Vec<bool>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Trace {
+    initial: Vec<Vec<bool>>,
+    events: Vec<Event>,
+}
+impl Trace {
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    // every event where the module either sent or received the pulse
+    pub fn filter(&self, module: NodeId) -> impl Iterator<Item = &Event> {
+        self.events
             .iter()
-            .fold(HashMap::new(), |mut acc, (id, module)| {
-                for destination in &module.destinations {
-                    acc.entry(destination.to_string())
-                        .or_insert_with(Vec::new)
-                        .push(id.to_string());
-                }
-                acc
-            });
-
-    for (id, module) in &mut modules {
-        match module.state {
-            State::ConjunctionUninitialized => {
-                module.state = State::Conjunction(
-                    destinations
-                        .get(id)
-                        .unwrap()
-                        .iter()
-                        .map(|dest| (dest.to_string(), false))
-                        .collect(),
-                );
+            .filter(move |event| event.pulse.from == module || event.pulse.to == module)
+    }
+
+    // the state of every module, by node id, once the given press finished.
+    // presses before recording started can't be reconstructed, so press zero
+    // is the state recording began with
+    pub fn state_at(&self, press: usize) -> Vec<Vec<bool>> {
+        self.events
+            .iter()
+            .take_while(|event| event.pulse.press <= press)
+            .fold(self.initial.clone(), |mut states, event| {
+                states[event.pulse.to] = event.state.clone();
+                states
+            })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub struct PulseCounts {
+    pub low: usize,
+    pub high: usize,
+}
+impl PulseCounts {
+    fn add(&mut self, signal: bool) {
+        match signal {
+            true => self.high += 1,
+            false => self.low += 1,
+        }
+    }
+}
+
+type Hook = Box<dyn FnMut(&Pulse)>;
+
+// modules are indexed by their node id in the wiring, destinations that are
+// never declared as a module (such as rx) have no state and swallow pulses
+pub struct Simulator {
+    wiring: Graph<String, ()>,
+    modules: Vec<Option<Box<dyn Module>>>,
+    prefixes: Vec<Option<char>>,
+    button: NodeId,
+    broadcaster: NodeId,
+    presses: usize,
+    received: Vec<PulseCounts>,
+    sent: Vec<PulseCounts>,
+    hooks: Vec<Hook>,
+    trace: Option<Trace>,
+}
+impl Simulator {
+    pub fn parse(input: &str) -> Result<Simulator, String> {
+        Simulator::parse_with(input, &Registry::default())
+    }
+
+    pub fn parse_with(input: &str, registry: &Registry) -> Result<Simulator, String> {
+        let mut wiring = Graph::directed();
+        let mut modules: HashMap<NodeId, Box<dyn Module>> = HashMap::new();
+        let mut prefixes = HashMap::new();
+
+        for line in input.lines() {
+            let (type_id, destinations) = line
+                .split_once(" -> ")
+                .ok_or_else(|| format!("expected a -> in {:?}", line))?;
+
+            let mut chars = type_id.chars();
+            let (module, name, prefix): (Box<dyn Module>, &str, _) = match chars.next() {
+                _ if type_id == "broadcaster" => (Box::new(Broadcaster), type_id, None),
+                Some(prefix) => match registry.kinds.get(&prefix) {
+                    Some(build) => (build(), chars.as_str(), Some(prefix)),
+                    None => return Err(format!("unknown module type in {:?}", line)),
+                },
+                None => return Err(format!("missing module name in {:?}", line)),
+            };
+
+            let id = wiring.intern(name.to_string());
+            if modules.insert(id, module).is_some() {
+                return Err(format!("module {} is declared twice", name));
+            }
+            prefixes.insert(id, prefix);
+            for destination in destinations.split(", ") {
+                let destination = wiring.intern(destination.to_string());
+                wiring.add_edge_ids(id, destination, ());
             }
-            _ => (),
         }
+
+        let broadcaster = wiring
+            .id("broadcaster")
+            .ok_or_else(|| "no broadcaster module".to_string())?;
+        let button = wiring.intern("button".to_string());
+        wiring.add_edge_ids(button, broadcaster, ());
+
+        let modules = wiring
+            .nodes()
+            .map(|id| {
+                modules.remove(&id).map(|mut module| {
+                    for from in wiring.incoming(id) {
+                        module.connect(*from);
+                    }
+                    module
+                })
+            })
+            .collect();
+
+        Ok(Simulator {
+            prefixes: wiring
+                .nodes()
+                .map(|id| prefixes.get(&id).copied().flatten())
+                .collect(),
+            received: vec![PulseCounts::default(); wiring.len()],
+            sent: vec![PulseCounts::default(); wiring.len()],
+            wiring,
+            modules,
+            button,
+            broadcaster,
+            presses: 0,
+            hooks: Vec::new(),
+            trace: None,
+        })
     }
 
-    modules
-        .into_iter()
-        .map(|(id, module)| (id.to_string(), RefCell::new(module)))
-        .collect()
-}
-
-#[derive(Debug, Clone)]
-struct Module {
-    id: String,
-    state: State,
-    destinations: Vec<String>,
-}
-impl Module {
-    fn parse(input: &str) -> Self {
-        let (type_id, destinations) = input.split_once(" -> ").unwrap();
-        let (type_val, id) = type_id.split_at(1);
-        let module_type = match type_val {
-            "b" => State::Broadcaster,
-            "%" => State::FlipFlop(false),
-            "&" => State::ConjunctionUninitialized,
-            _ => panic!("unknown module type"),
-        };
-
-        Self {
-            id: id.to_string(),
-            state: module_type,
-            destinations: destinations.split(", ").map(|s| s.to_string()).collect(),
+    pub fn id(&self, name: &str) -> Option<NodeId> {
+        self.wiring.id(name)
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        self.wiring.key(id)
+    }
+
+    // the prefix a module was declared with, none for the broadcaster and
+    // for anything that only ever appears as a destination
+    pub fn prefix(&self, id: NodeId) -> Option<char> {
+        self.prefixes[id]
+    }
+
+    // every module that can send a pulse which eventually reaches this one,
+    // including itself, but not the broadcaster and button that drive it all
+    fn upstream(&self, id: NodeId) -> HashSet<NodeId> {
+        let mut seen = HashSet::from([id]);
+        let mut queue = VecDeque::from([id]);
+        while let Some(current) = queue.pop_front() {
+            for from in self.wiring.incoming(current) {
+                if *from != self.broadcaster && *from != self.button && seen.insert(*from) {
+                    queue.push_back(*from);
+                }
+            }
         }
+        seen
     }
 
-    fn pulse(&mut self, from: &str, signal: bool) -> Option<bool> {
-        self.state.pulse(from, signal)
+    pub fn on_pulse(&mut self, hook: impl FnMut(&Pulse) + 'static) {
+        self.hooks.push(Box::new(hook));
     }
-}
 
-#[derive(Debug, Clone)]
-enum State {
-    Broadcaster,
-    FlipFlop(bool),
-    ConjunctionUninitialized,
-    Conjunction(HashMap<String, bool>),
-}
-impl State {
-    fn pulse(&mut self, from: &str, signal: bool) -> Option<bool> {
-        match (self, signal) {
-            (State::Broadcaster, _) => Some(signal),
-            (State::FlipFlop(_), true) => None,
-            (State::FlipFlop(value), false) => {
-                *value = !*value;
-                Some(*value)
+    // start recording every pulse from here on, presses are still numbered
+    // from the start so a trace begun late won't have the earlier presses
+    pub fn record(&mut self) {
+        self.trace = Some(Trace {
+            initial: self.states(),
+            events: Vec::new(),
+        });
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn states(&self) -> Vec<Vec<bool>> {
+        self.modules
+            .iter()
+            .map(|module| module.as_ref().map_or(Vec::new(), |m| m.state()))
+            .collect()
+    }
+
+    pub fn presses(&self) -> usize {
+        self.presses
+    }
+
+    pub fn received(&self, id: NodeId) -> PulseCounts {
+        self.received[id]
+    }
+
+    pub fn sent(&self, id: NodeId) -> PulseCounts {
+        self.sent[id]
+    }
+
+    pub fn total(&self) -> PulseCounts {
+        self.received
+            .iter()
+            .fold(PulseCounts::default(), |acc, counts| PulseCounts {
+                low: acc.low + counts.low,
+                high: acc.high + counts.high,
+            })
+    }
+
+    // pushes the button once and runs until every pulse has been delivered
+    pub fn press(&mut self) {
+        self.presses += 1;
+
+        let mut processing = VecDeque::new();
+        processing.push_back(Pulse {
+            press: self.presses,
+            from: self.button,
+            to: self.broadcaster,
+            signal: false,
+        });
+
+        while let Some(pulse) = processing.pop_front() {
+            self.sent[pulse.from].add(pulse.signal);
+            self.received[pulse.to].add(pulse.signal);
+            for hook in self.hooks.iter_mut() {
+                hook(&pulse);
+            }
+
+            if let Some(module) = &mut self.modules[pulse.to] {
+                if let Some(signal) = module.pulse(pulse.from, pulse.signal) {
+                    for (destination, _) in self.wiring.neighbors(pulse.to) {
+                        processing.push_back(Pulse {
+                            press: self.presses,
+                            from: pulse.to,
+                            to: *destination,
+                            signal,
+                        });
+                    }
+                }
             }
-            (State::Conjunction(states), _) => {
-                states.insert(from.to_string(), signal);
-                Some(!states.values().all(|v| *v))
+
+            if let Some(trace) = &mut self.trace {
+                trace.events.push(Event {
+                    pulse,
+                    state: self.modules[pulse.to]
+                        .as_ref()
+                        .map_or(Vec::new(), |m| m.state()),
+                });
             }
-            _ => panic!("unable to handle signal for state"),
         }
     }
 }
@@ -172,6 +462,7 @@ impl State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
 
     const EXAMPLE_ONE: &str = include_str!("input.example.one.txt");
     const EXAMPLE_TWO: &str = include_str!("input.example.two.txt");
@@ -186,6 +477,101 @@ mod tests {
 
     #[test]
     fn part_two_works() {
-        assert_eq!(221453937522197, part_two(INPUT, &"hf"), "input");
+        assert_eq!(221453937522197, part_two(INPUT), "input");
+    }
+
+    struct Inverter;
+    impl Module for Inverter {
+        fn pulse(&mut self, _from: NodeId, signal: bool) -> Option<bool> {
+            Some(!signal)
+        }
+    }
+
+    #[test]
+    fn parses_names_and_reports_errors() {
+        let simulator = Simulator::parse(EXAMPLE_ONE).unwrap();
+        assert!(simulator.id("broadcaster").is_some());
+        assert!(simulator.id("roadcaster").is_none());
+        assert!(simulator.id("inv").is_some());
+
+        assert!(Simulator::parse("broadcaster -> a\n!a -> b").is_err());
+        assert!(Simulator::parse("%a -> b").is_err());
+    }
+
+    #[test]
+    fn counts_pulses_and_supports_custom_modules() {
+        let mut simulator = Simulator::parse(EXAMPLE_TWO).unwrap();
+        let pulses = Rc::new(Cell::new(0));
+        let seen = pulses.clone();
+        simulator.on_pulse(move |_| seen.set(seen.get() + 1));
+
+        simulator.press();
+        let output = simulator.id("output").unwrap();
+        assert_eq!(PulseCounts { low: 1, high: 1 }, simulator.received(output));
+        assert_eq!(PulseCounts { low: 4, high: 4 }, simulator.total());
+        assert_eq!(8, pulses.get());
+
+        let mut registry = Registry::default();
+        registry.register('!', || Box::new(Inverter));
+        let mut simulator = Simulator::parse_with("broadcaster -> a\n!a -> b", &registry).unwrap();
+        simulator.press();
+        let b = simulator.id("b").unwrap();
+        assert_eq!(PulseCounts { low: 0, high: 1 }, simulator.received(b));
+        assert_eq!(
+            PulseCounts { low: 0, high: 1 },
+            simulator.sent(simulator.id("a").unwrap())
+        );
+    }
+
+    #[test]
+    fn analyzes_the_output_feeder() {
+        let analysis = analyze(INPUT, "rx").unwrap();
+        assert_eq!("hf", analysis.conjunction);
+        assert_eq!(4, analysis.branches.len());
+        assert_eq!(
+            221453937522197,
+            analysis.branches.iter().map(|b| b.cycle).fold(1, lcm)
+        );
+
+        assert_eq!(
+            Err("output isn't fed by a single conjunction".to_string()),
+            analyze(EXAMPLE_ONE, "inv")
+        );
+        // b is driven by a through inv, so their sub-circuits overlap
+        assert_eq!(
+            Err("a and b share module a".to_string()),
+            analyze(EXAMPLE_TWO, "output")
+        );
+    }
+
+    #[test]
+    fn records_and_replays_traces() {
+        let mut simulator = Simulator::parse(EXAMPLE_TWO).unwrap();
+        simulator.record();
+
+        let mut states = vec![simulator.states()];
+        for _ in 0..4 {
+            simulator.press();
+            states.push(simulator.states());
+        }
+
+        let trace = simulator.trace().unwrap();
+        // the circuit repeats every four presses, 7000 pulses per 1000 presses
+        assert_eq!(28, trace.events().len());
+        for (press, state) in states.iter().enumerate() {
+            assert_eq!(state, &trace.state_at(press), "press {}", press);
+        }
+
+        let a = simulator.id("a").unwrap();
+        let inv = simulator.id("inv").unwrap();
+        let from_a: Vec<_> = trace
+            .filter(a)
+            .filter(|event| event.pulse.from == a && event.pulse.press == 1)
+            .map(|event| (simulator.name(event.pulse.to), event.pulse.signal))
+            .collect();
+        assert_eq!(vec![("inv", true), ("con", true)], from_a);
+        assert_eq!(vec![false], trace.state_at(2)[a]);
+        assert_eq!(vec![true], trace.state_at(3)[a]);
+        assert_eq!(vec![true], trace.state_at(1)[inv]);
     }
 }
@@ -1,9 +1,7 @@
 use flat::coordinate::Coordinate;
 use flat::coordinate::Direction::{East, North, South, West};
 use flat::grid::Grid;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::collections::VecDeque;
+use graph::graph::{Graph, NodeId};
 
 pub fn part_one(input: &str) -> usize {
     solve(Grid::parse(input))
@@ -22,88 +20,87 @@ pub fn solve(grid: Grid) -> usize {
     let start = Coordinate(1, 0);
     let end = Coordinate(grid.bounds.x.max - 1, grid.bounds.y.max);
 
-    // pre-compute neighbors to memoize this logic a bit
-    let mut neighbors: HashMap<_, _> = grid
-        .points
-        .iter()
-        .filter(|(_, symbol)| **symbol != '#')
-        .map(|(coord, symbol)| {
-            (
-                coord,
-                match symbol {
-                    '>' => vec![(*coord + East, 1)],
-                    '<' => vec![(*coord + West, 1)],
-                    '^' => vec![(*coord + North, 1)],
-                    'v' => vec![(*coord + South, 1)],
-                    '.' => coord
-                        .cardinals()
-                        .iter()
-                        .filter(|c| grid.in_bounds(c))
-                        .filter(|coord| grid.is_not_equal(coord, &'#'))
-                        .copied()
-                        .map(|c| (c, 1))
-                        .collect(),
-                    _ => panic!("unexpected char: {}", symbol),
-                },
-            )
-        })
-        .collect();
-
-    // prune the true, collapsing all paths that are in a cooridor
-    // into a single step, this could be optimized, but it's also
-    // straight forward, so it'll do for now
-    loop {
-        let to_prune_option = neighbors
-            .iter()
-            .find(|(_, v)| v.len() == 2)
-            .map(|(k, v)| (*k, v.clone()));
-        if to_prune_option.is_none() {
-            break;
-        }
+    // each walkable tile is a node, with an edge of length one to every tile
+    // it can step onto
+    let mut trails: Graph<Coordinate, usize> = Graph::directed();
+    for (coord, symbol) in grid.points.iter().filter(|(_, symbol)| **symbol != '#') {
+        let steps = match symbol {
+            '>' => vec![*coord + East],
+            '<' => vec![*coord + West],
+            '^' => vec![*coord + North],
+            'v' => vec![*coord + South],
+            '.' => coord
+                .cardinals()
+                .into_iter()
+                .filter(|c| grid.in_bounds(c))
+                .filter(|coord| grid.is_not_equal(coord, &'#'))
+                .collect(),
+            _ => panic!("unexpected char: {}", symbol),
+        };
 
-        let to_prune = to_prune_option.unwrap();
-        let (left, left_dist) = to_prune.1[0];
-        let (right, right_dist) = to_prune.1[1];
-
-        neighbors
-            .get_mut(&left)
-            .unwrap()
-            .retain(|(k, _)| k != to_prune.0);
-        neighbors
-            .get_mut(&left)
-            .unwrap()
-            .push((right, left_dist + right_dist));
-        neighbors
-            .get_mut(&right)
-            .unwrap()
-            .retain(|(k, _)| k != to_prune.0);
-        neighbors
-            .get_mut(&right)
-            .unwrap()
-            .push((left, left_dist + right_dist));
-        neighbors.remove(to_prune.0);
+        let from = trails.intern(*coord);
+        for step in steps {
+            let to = trails.intern(step);
+            trails.add_edge_ids(from, to, 1);
+        }
     }
 
-    // simple dfs using queue of position, visited nodes & steps
-    let mut max_steps = 0;
-    let mut queue = VecDeque::new();
-    queue.push_front((start, HashSet::<Coordinate>::new(), 0));
+    // collapse every tile in a corridor, that is one which can be walked
+    // through in both directions, into a single longer edge between its ends
+    for node in trails.nodes() {
+        let neighbors = trails.neighbors(node).to_vec();
+        if neighbors.len() != 2 || trails.incoming(node).len() != 2 {
+            continue;
+        }
 
-    while let Some((coordinate, mut visited, steps)) = queue.pop_front() {
-        visited.insert(coordinate);
-        if steps > max_steps && coordinate == end {
-            max_steps = steps;
+        let (left, left_dist) = neighbors[0];
+        let (right, right_dist) = neighbors[1];
+        if trails.edge(left, node).is_none() || trails.edge(right, node).is_none() {
+            continue;
         }
 
-        neighbors
-            .get(&coordinate)
-            .unwrap()
-            .iter()
-            .filter(|(coord, _)| !visited.contains(coord))
-            .for_each(|(coord, dist)| queue.push_front((*coord, visited.clone(), steps + dist)));
+        trails.remove_edge(left, node);
+        trails.remove_edge(right, node);
+        trails.remove_edge(node, left);
+        trails.remove_edge(node, right);
+        trails.add_edge_ids(left, right, left_dist + right_dist);
+        trails.add_edge_ids(right, left, left_dist + right_dist);
+    }
+
+    let mut visited = vec![false; trails.len()];
+    longest_walk(
+        &trails,
+        trails.id(&start).unwrap(),
+        trails.id(&end).unwrap(),
+        &mut visited,
+    )
+    .unwrap()
+}
+
+// simple backtracking dfs, none if the end can't be reached from here
+fn longest_walk(
+    trails: &Graph<Coordinate, usize>,
+    node: NodeId,
+    end: NodeId,
+    visited: &mut [bool],
+) -> Option<usize> {
+    if node == end {
+        return Some(0);
+    }
+
+    visited[node] = true;
+    let mut longest = None;
+    for (next, dist) in trails.neighbors(node) {
+        if visited[*next] {
+            continue;
+        }
+        if let Some(walk) = longest_walk(trails, *next, end, visited) {
+            longest = longest.max(Some(walk + dist));
+        }
     }
+    visited[node] = false;
 
-    max_steps
+    longest
 }
 
 #[cfg(test)]
@@ -1,75 +1,208 @@
-use pathfinding::directed::bfs::bfs_reach;
-use std::collections::HashMap;
+use graph::export::Export;
+use graph::graph::Graph;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 
 pub fn part_one(input: &str) -> usize {
-    let mut graph = parse(input);
-    for _ in 0..3 {
-        let edge = most_connected_edge(&graph);
-        graph.get_mut(&edge.0).unwrap().remove(edge.1);
-        graph.get_mut(&edge.1).unwrap().remove(edge.0);
-    }
-
-    let subgraph_length =
-        bfs_reach(*graph.keys().next().unwrap(), |n| graph[n].iter().copied()).count();
-    subgraph_length * (graph.len() - subgraph_length)
+    let graph = parse(input);
+    let cut = cut_of_size(&graph, 3).expect("no cut of three wires");
+    cut.left.len() * cut.right.len()
 }
 
-pub fn parse(input: &str) -> HashMap<&str, HashSet<&str>> {
-    let mut graph: HashMap<&str, HashSet<&str>> = HashMap::new();
+pub fn parse(input: &str) -> Graph<&str, ()> {
+    let mut graph = Graph::undirected();
     for line in input.lines() {
         let (left, connections) = line.split_once(": ").unwrap();
+        let left = graph.intern(left);
         for right in connections.split_whitespace() {
-            graph.entry(left).or_default().insert(right);
-            graph.entry(right).or_default().insert(left);
+            let right = graph.intern(right);
+            if graph.edge(left, right).is_none() {
+                graph.add_edge_ids(left, right, ());
+            }
         }
     }
     graph
 }
 
-fn most_connected_edge<'a>(graph: &HashMap<&'a str, HashSet<&'a str>>) -> (&'a str, &'a str) {
-    let mut paths: HashMap<(&str, &str), usize> = HashMap::new();
+// the wiring as an undirected graph, with the given wires to cut highlighted
+pub fn export(input: &str, cut: &[(&str, &str)]) -> Export {
+    let graph = parse(input);
+    let mut export = Export::new(false);
+    for (left, right, _) in graph.edges() {
+        export.edge(graph.key(left), graph.key(right));
+    }
+    for (left, right) in cut {
+        export.highlight_edge(left, right);
+    }
+    export
+}
+
+#[derive(Debug)]
+pub struct Cut<'a> {
+    pub edges: Vec<(&'a str, &'a str)>,
+    pub left: HashSet<&'a str>,
+    pub right: HashSet<&'a str>,
+}
+
+// the global minimum cut is the smallest of the cuts separating any one node
+// from all the others, so we fix a source and try every other node as a sink
+pub fn minimum_cut<'a>(graph: &Graph<&'a str, ()>) -> Cut<'a> {
+    let network = Network::new(graph);
+    let mut best: Option<(usize, Vec<bool>)> = None;
+
+    for sink in 1..network.nodes.len() {
+        let limit = best.as_ref().map_or(usize::MAX, |(flow, _)| *flow);
+        let (flow, reachable) = network.max_flow(0, sink, limit);
+        if flow < limit {
+            best = Some((flow, reachable));
+        }
+    }
+
+    let (_, reachable) = best.expect("can't cut a graph with less than two nodes");
+    network.cut(&reachable)
+}
+
+// finds a cut of exactly the given number of edges, as soon as one shows up,
+// rather than proving it is the smallest there is
+pub fn cut_of_size<'a>(graph: &Graph<&'a str, ()>, size: usize) -> Option<Cut<'a>> {
+    let network = Network::new(graph);
+
+    (1..network.nodes.len()).find_map(|sink| {
+        let (flow, reachable) = network.max_flow(0, sink, size + 1);
+        match flow == size {
+            true => Some(network.cut(&reachable)),
+            false => None,
+        }
+    })
+}
+
+// every wire has a capacity of one in both directions, stored as a pair of
+// arcs which are each other's reverse so flow can be pushed back either way
+struct Network<'a> {
+    nodes: Vec<&'a str>,
+    arcs: Vec<Vec<usize>>,
+    targets: Vec<usize>,
+}
+impl<'a> Network<'a> {
+    fn new(graph: &Graph<&'a str, ()>) -> Network<'a> {
+        let nodes: Vec<_> = graph.nodes().map(|id| *graph.key(id)).collect();
+        let mut arcs = vec![Vec::new(); nodes.len()];
+        let mut targets = Vec::new();
+        for (from, to, _) in graph.edges() {
+            arcs[from].push(targets.len());
+            targets.push(to);
+            arcs[to].push(targets.len());
+            targets.push(from);
+        }
+
+        Network {
+            nodes,
+            arcs,
+            targets,
+        }
+    }
 
-    // loop through all the keys
-    for start in graph.keys().copied() {
-        let mut queue = VecDeque::new();
-        queue.push_back(start);
+    // edmonds-karp, giving up once the flow reaches the limit. returns the
+    // flow along with which nodes are still reachable from the source
+    fn max_flow(&self, source: usize, sink: usize, limit: usize) -> (usize, Vec<bool>) {
+        let mut flow = vec![0_i32; self.targets.len()];
+        let mut total = 0;
 
-        let mut visited = HashSet::new();
-        visited.insert(start);
+        loop {
+            let mut via: Vec<Option<usize>> = vec![None; self.nodes.len()];
+            let mut reachable = vec![false; self.nodes.len()];
+            reachable[source] = true;
 
-        while let Some(node) = queue.pop_front() {
-            for n in graph[&node].iter().copied() {
-                if visited.contains(&n) {
-                    continue;
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                if node == sink {
+                    break;
+                }
+                for arc in self.arcs[node].iter().copied() {
+                    let next = self.targets[arc];
+                    if !reachable[next] && flow[arc] < 1 {
+                        reachable[next] = true;
+                        via[next] = Some(arc);
+                        queue.push_back(next);
+                    }
                 }
+            }
 
-                queue.push_back(n);
-                visited.insert(n);
+            if !reachable[sink] || total >= limit {
+                return (total, reachable);
+            }
 
-                let edge = match n < node {
-                    true => (n, node),
-                    false => (node, n),
-                };
-                *paths.entry(edge).or_default() += 1;
+            let mut node = sink;
+            while let Some(arc) = via[node] {
+                flow[arc] += 1;
+                flow[arc ^ 1] -= 1;
+                node = self.targets[arc ^ 1];
             }
+            total += 1;
+        }
+    }
+
+    fn cut(&self, reachable: &[bool]) -> Cut<'a> {
+        let mut edges: Vec<_> = (0..self.targets.len())
+            .step_by(2)
+            .map(|arc| (self.targets[arc ^ 1], self.targets[arc]))
+            .filter(|(from, to)| reachable[*from] != reachable[*to])
+            .map(|(from, to)| {
+                let (from, to) = (self.nodes[from], self.nodes[to]);
+                (from.min(to), from.max(to))
+            })
+            .collect();
+        edges.sort();
+
+        let (left, right) = (0..self.nodes.len()).partition(|node| reachable[*node]);
+        let names = |side: Vec<usize>| side.into_iter().map(|node| self.nodes[node]).collect();
+
+        Cut {
+            edges,
+            left: names(left),
+            right: names(right),
         }
     }
-    paths.into_iter().max_by_key(|&(_, v)| v).unwrap().0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    //static INPUT: &str = include_str!("input.txt");
+    static INPUT: &str = include_str!("input.txt");
     static EXAMPLE: &str = include_str!("input.example.txt");
 
     #[test]
     fn part_one_works() {
         assert_eq!(54, part_one(EXAMPLE));
-        // takes a little over a second to run
-        //assert_eq!(596376, part_one(INPUT));
+        assert_eq!(596376, part_one(INPUT));
+    }
+
+    #[test]
+    fn finds_minimum_cut() {
+        let graph = parse(EXAMPLE);
+        let cut = minimum_cut(&graph);
+        assert_eq!(
+            vec![("bvb", "cmg"), ("hfx", "pzl"), ("jqt", "nvd")],
+            cut.edges
+        );
+        assert_eq!(54, cut.left.len() * cut.right.len());
+        assert!(cut.left.contains("hfx") != cut.left.contains("pzl"));
+
+        assert_eq!(cut.edges, cut_of_size(&graph, 3).unwrap().edges);
+        assert!(cut_of_size(&graph, 2).is_none());
+
+        assert_eq!(3, minimum_cut(&parse(INPUT)).edges.len());
+    }
+
+    #[test]
+    fn exports_wiring() {
+        let cut = [("hfx", "pzl"), ("bvb", "cmg"), ("nvd", "jqt")];
+        let dot = export(EXAMPLE, &cut).to_dot();
+        assert!(dot.starts_with("graph {"));
+        assert_eq!(33, dot.matches(" -- ").count());
+        assert_eq!(3, dot.matches("color=red").count());
+        assert!(dot.contains("\"hfx\" -- \"pzl\" [color=red, penwidth=3];"));
+        assert!(dot.contains("\"jqt\" -- \"nvd\" [color=red, penwidth=3];"));
     }
 }
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+// collects the nodes and edges of a graph so it can be written out as
+// graphviz dot, or a json adjacency list, for inspecting its structure
+pub struct Export {
+    directed: bool,
+    nodes: BTreeSet<String>,
+    edges: BTreeMap<(String, String), Option<String>>,
+    highlighted_nodes: BTreeSet<String>,
+    highlighted_edges: BTreeSet<(String, String)>,
+}
+
+impl Export {
+    pub fn new(directed: bool) -> Export {
+        Export {
+            directed,
+            nodes: BTreeSet::new(),
+            edges: BTreeMap::new(),
+            highlighted_nodes: BTreeSet::new(),
+            highlighted_edges: BTreeSet::new(),
+        }
+    }
+
+    pub fn node(&mut self, node: &str) {
+        self.nodes.insert(node.to_string());
+    }
+
+    // undirected edges are the same either way around, so adding b -> a after
+    // a -> b doesn't produce a second edge
+    pub fn edge(&mut self, from: &str, to: &str) {
+        self.node(from);
+        self.node(to);
+        self.edges.entry(self.key(from, to)).or_insert(None);
+    }
+
+    pub fn labeled_edge(&mut self, from: &str, to: &str, label: &str) {
+        self.node(from);
+        self.node(to);
+        self.edges
+            .insert(self.key(from, to), Some(label.to_string()));
+    }
+
+    pub fn highlight_node(&mut self, node: &str) {
+        self.highlighted_nodes.insert(node.to_string());
+    }
+
+    pub fn highlight_edge(&mut self, from: &str, to: &str) {
+        self.highlighted_edges.insert(self.key(from, to));
+    }
+
+    pub fn to_dot(&self) -> String {
+        let (kind, connector) = match self.directed {
+            true => ("digraph", "->"),
+            false => ("graph", "--"),
+        };
+
+        let mut lines = vec![format!("{} {{", kind)];
+        for node in &self.nodes {
+            match self.highlighted_nodes.contains(node) {
+                true => lines.push(format!(
+                    "    \"{}\" [style=filled, fillcolor=red];",
+                    escape(node)
+                )),
+                false => lines.push(format!("    \"{}\";", escape(node))),
+            }
+        }
+
+        for ((from, to), label) in &self.edges {
+            let mut attributes = Vec::new();
+            if let Some(label) = label {
+                attributes.push(format!("label=\"{}\"", escape(label)));
+            }
+            if self.highlighted_edges.contains(&(from.clone(), to.clone())) {
+                attributes.push("color=red, penwidth=3".to_string());
+            }
+
+            let attributes = match attributes.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", attributes.join(", ")),
+            };
+            lines.push(format!(
+                "    \"{}\" {} \"{}\"{};",
+                escape(from),
+                connector,
+                escape(to),
+                attributes
+            ));
+        }
+
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    // each node mapped to its neighbors, undirected edges are listed from both
+    // ends so every node can be looked up on its own
+    pub fn to_json(&self) -> String {
+        let mut adjacency: BTreeMap<&str, BTreeSet<&str>> = self
+            .nodes
+            .iter()
+            .map(|node| (node.as_str(), BTreeSet::new()))
+            .collect();
+
+        for (from, to) in self.edges.keys() {
+            adjacency.get_mut(from.as_str()).unwrap().insert(to);
+            if !self.directed {
+                adjacency.get_mut(to.as_str()).unwrap().insert(from);
+            }
+        }
+
+        let entries: Vec<_> = adjacency
+            .iter()
+            .map(|(node, neighbors)| {
+                let neighbors: Vec<_> = neighbors
+                    .iter()
+                    .map(|n| format!("\"{}\"", escape(n)))
+                    .collect();
+                format!("  \"{}\": [{}]", escape(node), neighbors.join(", "))
+            })
+            .collect();
+
+        format!("{{\n{}\n}}", entries.join(",\n"))
+    }
+
+    fn key(&self, from: &str, to: &str) -> (String, String) {
+        match !self.directed && to < from {
+            true => (to.to_string(), from.to_string()),
+            false => (from.to_string(), to.to_string()),
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_undirected_graphs() {
+        let mut export = Export::new(false);
+        export.edge("b", "a");
+        export.edge("a", "b");
+        export.edge("b", "c");
+        export.highlight_node("a");
+        export.highlight_edge("c", "b");
+
+        assert_eq!(
+            "graph {\n    \"a\" [style=filled, fillcolor=red];\n    \"b\";\n    \"c\";\n    \"a\" -- \"b\";\n    \"b\" -- \"c\" [color=red, penwidth=3];\n}",
+            export.to_dot()
+        );
+        assert_eq!(
+            "{\n  \"a\": [\"b\"],\n  \"b\": [\"a\", \"c\"],\n  \"c\": [\"b\"]\n}",
+            export.to_json()
+        );
+    }
+
+    #[test]
+    fn exports_directed_graphs() {
+        let mut export = Export::new(true);
+        export.labeled_edge("a", "b", "L");
+        export.labeled_edge("b", "a", "R");
+
+        assert_eq!(
+            "digraph {\n    \"a\";\n    \"b\";\n    \"a\" -> \"b\" [label=\"L\"];\n    \"b\" -> \"a\" [label=\"R\"];\n}",
+            export.to_dot()
+        );
+        assert_eq!(
+            "{\n  \"a\": [\"b\"],\n  \"b\": [\"a\"]\n}",
+            export.to_json()
+        );
+    }
+}
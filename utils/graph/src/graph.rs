@@ -0,0 +1,318 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+pub type NodeId = usize;
+
+// nodes are interned the first time they're seen, handing out dense ids so
+// the hot loops of a solution can index into vectors rather than hash keys
+pub struct Graph<K, W> {
+    directed: bool,
+    keys: Vec<K>,
+    ids: HashMap<K, NodeId>,
+    outgoing: Vec<Vec<(NodeId, W)>>,
+    incoming: Vec<Vec<NodeId>>,
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> Graph<K, W> {
+    pub fn directed() -> Graph<K, W> {
+        Graph::new(true)
+    }
+
+    pub fn undirected() -> Graph<K, W> {
+        Graph::new(false)
+    }
+
+    fn new(directed: bool) -> Graph<K, W> {
+        Graph {
+            directed,
+            keys: Vec::new(),
+            ids: HashMap::new(),
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+        }
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    pub fn intern(&mut self, key: K) -> NodeId {
+        if let Some(id) = self.ids.get(&key) {
+            return *id;
+        }
+
+        let id = self.keys.len();
+        self.ids.insert(key.clone(), id);
+        self.keys.push(key);
+        self.outgoing.push(Vec::new());
+        self.incoming.push(Vec::new());
+        id
+    }
+
+    pub fn id<Q>(&self, key: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.ids.get(key).copied()
+    }
+
+    pub fn key(&self, id: NodeId) -> &K {
+        &self.keys[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn nodes(&self) -> std::ops::Range<NodeId> {
+        0..self.keys.len()
+    }
+
+    // undirected edges are stored in both directions, so neighbors and
+    // incoming work the same from either end
+    pub fn add_edge(&mut self, from: K, to: K, weight: W) -> (NodeId, NodeId) {
+        let from = self.intern(from);
+        let to = self.intern(to);
+        self.add_edge_ids(from, to, weight);
+        (from, to)
+    }
+
+    pub fn add_edge_ids(&mut self, from: NodeId, to: NodeId, weight: W) {
+        self.outgoing[from].push((to, weight.clone()));
+        self.incoming[to].push(from);
+
+        if !self.directed && from != to {
+            self.outgoing[to].push((from, weight));
+            self.incoming[from].push(to);
+        }
+    }
+
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) {
+        self.outgoing[from].retain(|(id, _)| *id != to);
+        self.incoming[to].retain(|id| *id != from);
+
+        if !self.directed {
+            self.outgoing[to].retain(|(id, _)| *id != from);
+            self.incoming[from].retain(|id| *id != to);
+        }
+    }
+
+    pub fn neighbors(&self, id: NodeId) -> &[(NodeId, W)] {
+        &self.outgoing[id]
+    }
+
+    pub fn incoming(&self, id: NodeId) -> &[NodeId] {
+        &self.incoming[id]
+    }
+
+    pub fn edge(&self, from: NodeId, to: NodeId) -> Option<&W> {
+        self.outgoing[from]
+            .iter()
+            .find(|(id, _)| *id == to)
+            .map(|(_, weight)| weight)
+    }
+
+    // every edge once, undirected edges are only given from the lower id
+    pub fn edges(&self) -> impl Iterator<Item = (NodeId, NodeId, &W)> {
+        self.outgoing
+            .iter()
+            .enumerate()
+            .flat_map(move |(from, edges)| {
+                edges
+                    .iter()
+                    .filter(move |(to, _)| self.directed || from <= *to)
+                    .map(move |(to, weight)| (from, *to, weight))
+            })
+    }
+
+    // directed graphs are treated as undirected here, so these are the
+    // weakly connected components, each sorted and ordered by their first id
+    pub fn connected_components(&self) -> Vec<Vec<NodeId>> {
+        let mut seen = vec![false; self.len()];
+        let mut components = Vec::new();
+
+        for start in self.nodes() {
+            if seen[start] {
+                continue;
+            }
+
+            seen[start] = true;
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(id) = queue.pop_front() {
+                let outgoing = self.outgoing[id].iter().map(|(next, _)| *next);
+                for next in outgoing.chain(self.incoming[id].iter().copied()) {
+                    if !seen[next] {
+                        seen[next] = true;
+                        component.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            component.sort();
+            components.push(component);
+        }
+
+        components
+    }
+
+    // kahn's algorithm, none if the graph has a cycle. an undirected edge
+    // counts as a cycle of two, so this only makes sense for directed graphs
+    pub fn topological_order(&self) -> Option<Vec<NodeId>> {
+        let mut remaining: Vec<_> = self.incoming.iter().map(|i| i.len()).collect();
+        let mut queue: VecDeque<_> = self.nodes().filter(|id| remaining[*id] == 0).collect();
+        let mut order = Vec::with_capacity(self.len());
+
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for (next, _) in &self.outgoing[id] {
+                remaining[*next] -= 1;
+                if remaining[*next] == 0 {
+                    queue.push_back(*next);
+                }
+            }
+        }
+
+        match order.len() == self.len() {
+            true => Some(order),
+            false => None,
+        }
+    }
+
+    // tarjan's algorithm, each component sorted, and the components ordered so
+    // that everything a component leads to comes before it
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        let mut tarjan = Tarjan {
+            index: vec![None; self.len()],
+            lowlink: vec![0; self.len()],
+            on_stack: vec![false; self.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+
+        for id in self.nodes() {
+            if tarjan.index[id].is_none() {
+                tarjan.visit(self, id);
+            }
+        }
+        tarjan.components
+    }
+}
+
+struct Tarjan {
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<NodeId>,
+    next_index: usize,
+    components: Vec<Vec<NodeId>>,
+}
+impl Tarjan {
+    fn visit<K: Hash + Eq + Clone, W: Clone>(&mut self, graph: &Graph<K, W>, id: NodeId) {
+        self.index[id] = Some(self.next_index);
+        self.lowlink[id] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(id);
+        self.on_stack[id] = true;
+
+        for (next, _) in graph.neighbors(id) {
+            match self.index[*next] {
+                None => {
+                    self.visit(graph, *next);
+                    self.lowlink[id] = self.lowlink[id].min(self.lowlink[*next]);
+                }
+                Some(index) if self.on_stack[*next] => {
+                    self.lowlink[id] = self.lowlink[id].min(index);
+                }
+                _ => (),
+            }
+        }
+
+        // the root of a component, everything above it on the stack belongs to it
+        if Some(self.lowlink[id]) == self.index[id] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == id {
+                    break;
+                }
+            }
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_and_connects_nodes() {
+        let mut graph: Graph<&str, usize> = Graph::undirected();
+        let (a, b) = graph.add_edge("a", "b", 3);
+        graph.add_edge("b", "c", 4);
+        graph.add_edge("d", "e", 1);
+
+        assert_eq!(5, graph.len());
+        assert_eq!(Some(b), graph.id("b"));
+        assert_eq!(&"a", graph.key(a));
+        assert_eq!(Some(&3), graph.edge(b, a));
+        assert_eq!(2, graph.neighbors(b).len());
+        assert_eq!(3, graph.edges().count());
+        assert_eq!(
+            vec![vec![0, 1, 2], vec![3, 4]],
+            graph.connected_components()
+        );
+
+        graph.remove_edge(b, a);
+        assert_eq!(None, graph.edge(a, b));
+        assert_eq!(3, graph.connected_components().len());
+    }
+
+    #[test]
+    fn orders_directed_graphs() {
+        let mut graph: Graph<String, ()> = Graph::directed();
+        graph.add_edge("c".to_string(), "d".to_string(), ());
+        graph.add_edge("a".to_string(), "b".to_string(), ());
+        graph.add_edge("b".to_string(), "c".to_string(), ());
+
+        let order: Vec<_> = graph
+            .topological_order()
+            .unwrap()
+            .into_iter()
+            .map(|id| graph.key(id).as_str())
+            .collect();
+        assert_eq!(vec!["a", "b", "c", "d"], order);
+        assert_eq!(vec![3], graph.incoming(0));
+        assert_eq!(1, graph.connected_components().len());
+
+        graph.add_edge("d".to_string(), "b".to_string(), ());
+        assert_eq!(None, graph.topological_order());
+    }
+
+    #[test]
+    fn finds_strongly_connected_components() {
+        let mut graph: Graph<&str, ()> = Graph::directed();
+        graph.add_edge("a", "b", ());
+        graph.add_edge("b", "a", ());
+        graph.add_edge("b", "c", ());
+        graph.add_edge("c", "d", ());
+        graph.add_edge("d", "c", ());
+        graph.add_edge("d", "e", ());
+
+        assert_eq!(
+            vec![vec![4], vec![2, 3], vec![0, 1]],
+            graph.strongly_connected_components()
+        );
+    }
+}
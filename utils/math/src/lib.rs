@@ -1,15 +1,65 @@
-use std::cmp::{max, min};
-
 pub fn lcm(left: usize, right: usize) -> usize {
-    let max_val = max(left, right);
-    let min_val = min(left, right);
+    match (left, right) {
+        (0, _) | (_, 0) => 0,
+        _ => left / gcd(left, right) * right,
+    }
+}
+
+pub fn gcd(left: usize, right: usize) -> usize {
+    match right {
+        0 => left,
+        _ => gcd(right, left % right),
+    }
+}
 
-    for i in 1..=min_val {
-        let multiple = max_val * i;
-        if multiple % min_val == 0 {
-            return multiple;
+// generalized chinese remainder theorem, the moduli don't need to be coprime.
+// finds the smallest non-negative value that is congruent to both remainders
+// and the modulus it repeats with, or none if the congruences contradict
+pub fn crt(
+    left_remainder: usize,
+    left_modulus: usize,
+    right_remainder: usize,
+    right_modulus: usize,
+) -> Option<(usize, usize)> {
+    let (m1, m2) = (left_modulus as i128, right_modulus as i128);
+    let (a1, a2) = (left_remainder as i128 % m1, right_remainder as i128 % m2);
+    let (divisor, inverse, _) = extended_gcd(m1, m2);
+
+    if (a2 - a1) % divisor != 0 {
+        return None;
+    }
+
+    let modulus = m1 / divisor * m2;
+    let step = ((a2 - a1) / divisor * inverse).rem_euclid(m2 / divisor);
+    let value = (a1 + m1 * step).rem_euclid(modulus);
+
+    Some((value as usize, modulus as usize))
+}
+
+// returns (gcd, x, y) such that left * x + right * y = gcd
+fn extended_gcd(left: i128, right: i128) -> (i128, i128, i128) {
+    match right {
+        0 => (left, 1, 0),
+        _ => {
+            let (divisor, x, y) = extended_gcd(right, left % right);
+            (divisor, y, x - (left / right) * y)
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    max_val * min_val
+    #[test]
+    fn crt_combines_congruences() {
+        assert_eq!(
+            Some((23, 105)),
+            crt(2, 3, 3, 5).and_then(|(v, m)| crt(v, m, 2, 7))
+        );
+        assert_eq!(Some((10, 12)), crt(4, 6, 2, 4));
+        assert_eq!(None, crt(1, 6, 2, 4));
+        assert_eq!(12, lcm(4, 6));
+        assert_eq!(2, gcd(4, 6));
+    }
 }